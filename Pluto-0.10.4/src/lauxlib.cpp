@@ -1099,7 +1099,7 @@ LUALIB_API const char *luaL_gsub (lua_State *L, const char *s,
 
 
 static void *l_alloc (void *ud, void *ptr, size_t osize, size_t nsize) {
-#ifndef PLUTO_MEMORY_LIMIT
+#if !defined(PLUTO_MEMORY_LIMIT) || defined(PLUTOW_MEMORY_RUNTIME)
   (void)ud; (void)osize;  /* not used */
 #endif
   if (nsize == 0) {
@@ -1107,7 +1107,7 @@ static void *l_alloc (void *ud, void *ptr, size_t osize, size_t nsize) {
     return NULL;
   }
   else {
-#ifdef PLUTO_MEMORY_LIMIT
+#if defined(PLUTO_MEMORY_LIMIT) && !defined(PLUTOW_MEMORY_RUNTIME)  /* otherwise enforced by lmem */
     if (ud  /* state has finished opening? */
       && (!ptr || nsize > osize)  /* new allocation or increasing existing allocation? */
       && gettotalbytes(reinterpret_cast<global_State*>(ud)) >= PLUTO_MEMORY_LIMIT  /* limit reached? */
@@ -44,7 +44,29 @@
 /*
 ** Macro to call the allocation function.
 */
+#ifdef PLUTOW_MEMORY_RUNTIME
+/*
+** Fails allocations that would exceed the memory limit of the state, so
+** they go through the emergency collection like any other failed
+** allocation, and keeps track of the peak memory usage.
+*/
+static void *limitedfrealloc (global_State *g, void *block,
+                              size_t os, size_t ns) {
+  size_t oldsize = (block == NULL) ? 0 : os;  /* 'os' is a tag for new blocks */
+  lu_mem total = gettotalbytes(g);
+  if (ns > oldsize && g->memory_limit != 0
+      && total + (ns - oldsize) > g->memory_limit)
+    return NULL;  /* limit reached */
+  void *newblock = (*g->frealloc)(g->ud, block, os, ns);
+  if (newblock != NULL && ns > oldsize && total + (ns - oldsize) > g->memory_peak)
+    g->memory_peak = total + (ns - oldsize);
+  return newblock;
+}
+
+#define callfrealloc(g,block,os,ns)    limitedfrealloc(g, block, os, ns)
+#else
 #define callfrealloc(g,block,os,ns)    ((*g->frealloc)(g->ud, block, os, ns))
+#endif
 
 
 /*
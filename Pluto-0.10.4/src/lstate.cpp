@@ -12,6 +12,7 @@
 
 #include <stddef.h>
 #include <string.h>
+#include <limits>
 
 #include "lua.h"
 
@@ -27,7 +28,9 @@
 #include "ltable.h"
 #include "ltm.h"
 
+#ifndef PLUTOW_NO_HTTPLIB
 #include "vendor/Soup/soup/DetachedScheduler.hpp"
+#endif
 
 
 
@@ -279,7 +282,7 @@ static void close_state (lua_State *L) {
     L->ci = &L->base_ci;  /* unwind CallInfo list */
     luaD_closeprotected(L, 1, LUA_OK);  /* close all upvalues */
     luaC_freeallobjects(L);  /* collect all objects */
-#if !SOUP_WASM
+#if !SOUP_WASM && !defined(PLUTOW_NO_HTTPLIB)
     if (g->scheduler) {
       delete reinterpret_cast<soup::DetachedScheduler*>(g->scheduler);
     }
@@ -406,6 +409,14 @@ LUA_API lua_State *lua_newstate (lua_Alloc f, void *ud) {
   g->twups = NULL;
   g->totalbytes = sizeof(LG);
   g->GCdebt = 0;
+#ifdef PLUTOW_MEMORY_RUNTIME
+#ifdef PLUTO_MEMORY_LIMIT
+  g->memory_limit = PLUTO_MEMORY_LIMIT;
+#else
+  g->memory_limit = 0;  /* no limit */
+#endif
+  g->memory_peak = sizeof(LG);
+#endif
   g->lastatomic = 0;
   setivalue(&g->nilvalue, 0);  /* to signal that state is not yet built */
   setgcparam(g->gcpause, LUAI_GCPAUSE);
@@ -471,8 +482,18 @@ LUA_API lua_State *lua_newstate (lua_Alloc f, void *ud) {
 #endif
 
   g->scheduler = nullptr;
-#ifdef PLUTO_ETL_ENABLE
+#ifdef PLUTOW_ETL_NO_DEADLINE
+  g->deadline = std::numeric_limits<std::time_t>::max();
+#elif defined(PLUTO_ETL_ENABLE)
   g->deadline = std::chrono::duration_cast<std::chrono::nanoseconds>(std::chrono::steady_clock::now().time_since_epoch()).count() + PLUTO_ETL_NANOS;
+#endif
+#ifdef PLUTOW_ILP_RUNTIME
+  g->ilp_max_iterations = PLUTO_ILP_MAX_ITERATIONS;
+#ifdef PLUTO_ILP_SILENT_BREAK
+  g->ilp_silent_break = true;
+#else
+  g->ilp_silent_break = false;
+#endif
 #endif
   if (luaD_rawrunprotected(L, f_luaopen, NULL) != LUA_OK) {
     /* memory allocation error: free partial state */
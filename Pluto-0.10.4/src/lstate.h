@@ -335,6 +335,14 @@ typedef struct global_State {
 #ifdef PLUTO_ETL_ENABLE
   std::time_t deadline;  /* internal use only; do not use this in your own code. */
 #endif
+#ifdef PLUTOW_MEMORY_RUNTIME
+  lu_mem memory_limit;  /* internal use only; do not use this in your own code. */
+  lu_mem memory_peak;  /* internal use only; do not use this in your own code. */
+#endif
+#ifdef PLUTOW_ILP_RUNTIME
+  int ilp_max_iterations;  /* internal use only; do not use this in your own code. */
+  bool ilp_silent_break;  /* internal use only; do not use this in your own code. */
+#endif
 #ifndef PLUTO_NO_DEFAULT_TABLE_METATABLE
   TValue table_mt;  /* internal use only; do not use this in your own code. */
 #endif
@@ -927,12 +927,26 @@
 // If defined, Pluto won't throw an error and instead just break out of the loop.
 //#define PLUTO_ILP_SILENT_BREAK
 
+// If defined, the maximum iterations and silent break are read from the global state, so they can be changed per state at runtime.
+// PLUTO_ILP_MAX_ITERATIONS and PLUTO_ILP_SILENT_BREAK only provide the initial values.
+//#define PLUTOW_ILP_RUNTIME
+
+#ifdef PLUTOW_ILP_RUNTIME
+  #define pluto_ilp_limit(L) ((L)->l_G->ilp_max_iterations)
+#else
+  #define pluto_ilp_limit(L) PLUTO_ILP_MAX_ITERATIONS
+#endif
+
 // Allows you to customise how an ILP violation is raised to the runtime (or not).
-#ifdef PLUTO_ILP_SILENT_BREAK
+#if defined(PLUTO_ILP_SILENT_BREAK) && !defined(PLUTOW_ILP_RUNTIME)
   #define PLUTO_ILP_ERROR ;
 #else
   #ifndef PLUTO_ILP_ERROR
-    #define PLUTO_ILP_ERROR luaG_runerror(L, "infinite loop detected (exceeded max iterations: %d)", PLUTO_ILP_MAX_ITERATIONS);
+    #ifdef PLUTOW_ILP_RUNTIME
+      #define PLUTO_ILP_ERROR if (!(L)->l_G->ilp_silent_break) luaG_runerror(L, "infinite loop detected (exceeded max iterations: %d)", pluto_ilp_limit(L));
+    #else
+      #define PLUTO_ILP_ERROR luaG_runerror(L, "infinite loop detected (exceeded max iterations: %d)", PLUTO_ILP_MAX_ITERATIONS);
+    #endif
   #endif
 #endif
 
@@ -949,6 +963,14 @@
 //#define PLUTO_ETL_ENABLE
 
 #ifdef PLUTO_ETL_ENABLE
+// If defined, the deadline can be set, extended and cleared per state at runtime.
+// States start without a deadline, unless PLUTO_ETL_NANOS is defined.
+//#define PLUTOW_ETL_RUNTIME
+
+#if defined(PLUTOW_ETL_RUNTIME) && !defined(PLUTO_ETL_NANOS)
+#define PLUTOW_ETL_NO_DEADLINE
+#endif
+
 /*
 ** This is the maximum amount of nanoseconds the VM is allowed to run.
 */
@@ -975,6 +997,11 @@
 
 //#define PLUTO_MEMORY_LIMIT 64'000'000 /* 64 MB (megabytes, not mebibytes!) */
 
+// If defined, the memory limit is read from the global state, so it can be changed per state at runtime,
+// and the peak memory usage is tracked. This applies to all states, not only those created by luaL_newstate.
+// PLUTO_MEMORY_LIMIT only provides the initial value.
+//#define PLUTOW_MEMORY_RUNTIME
+
 /*
 ** {====================================================================
 ** Pluto Configuration: VM Dump
@@ -993,11 +1020,15 @@
 */
 
 // Opcodes listed in this structure are a blacklist. They not be printed when VM dumping.
+#ifndef vmDumpIgnore
 #define vmDumpIgnore
+#endif
 
 
 // Opcodes listed in this structure are a whitelist. They are only printed when VM dumping.
+#ifndef vmDumpAllow
 #define vmDumpAllow
+#endif
 
 // If defined, Pluto will use vmDumpAllow instead of vmDumpIgnore.
 //#define PLUTO_VMDUMP_WHITELIST
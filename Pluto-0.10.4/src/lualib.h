@@ -64,25 +64,57 @@ namespace Pluto {
   extern const PreloadedLibrary preloaded_canvas;
 
   inline const PreloadedLibrary* const all_preloaded[] = {
+#ifndef PLUTOW_NO_CRYPTOLIB
     &preloaded_crypto,
+#endif
+#ifndef PLUTOW_NO_JSONLIB
     &preloaded_json,
+#endif
+#ifndef PLUTOW_NO_BASE32LIB
     &preloaded_base32,
+#endif
+#ifndef PLUTOW_NO_BASE64LIB
     &preloaded_base64,
+#endif
+#ifndef PLUTOW_NO_ASSERTLIB
     &preloaded_assert,
+#endif
+#ifndef PLUTOW_NO_VECTOR3LIB
     &preloaded_vector3,
+#endif
+#ifndef PLUTOW_NO_URLLIB
     &preloaded_url,
+#endif
+#ifndef PLUTOW_NO_STARLIB
     &preloaded_star,
+#endif
+#ifndef PLUTOW_NO_CATLIB
     &preloaded_cat,
+#endif
+#ifndef PLUTOW_NO_HTTPLIB
     &preloaded_http,
+#endif
+#ifndef PLUTOW_NO_SCHEDULERLIB
     &preloaded_scheduler,
-#ifndef __EMSCRIPTEN__
+#endif
+#if !defined(__EMSCRIPTEN__) && !defined(PLUTOW_NO_SOCKETLIB)
     &preloaded_socket,
 #endif
+#ifndef PLUTOW_NO_BIGINTLIB
     &preloaded_bigint,
+#endif
+#ifndef PLUTOW_NO_XMLLIB
     &preloaded_xml,
+#endif
+#ifndef PLUTOW_NO_REGEXLIB
     &preloaded_regex,
+#endif
+#ifndef PLUTOW_NO_FFILIB
     &preloaded_ffi,
+#endif
+#ifndef PLUTOW_NO_CANVASLIB
     &preloaded_canvas,
+#endif
   };
 
   extern const ConstexprLibrary constexpr_io;
@@ -2099,8 +2099,9 @@ void luaV_execute (lua_State *L, CallInfo *ci) {
           sequentialJumps++;
         }
         else sequentialJumps = 0;
-        if (l_unlikely(sequentialJumps == PLUTO_ILP_MAX_ITERATIONS)) {
+        if (l_unlikely(sequentialJumps >= pluto_ilp_limit(L))) {
           savepc(L);
+          sequentialJumps = 0;  /* the next loop gets the full limit again */
           PLUTO_ILP_ERROR;
           vmbreak;
         }
@@ -2219,7 +2220,8 @@ void luaV_execute (lua_State *L, CallInfo *ci) {
           sequentialJumps++;
         }
         else sequentialJumps = 0;
-        if (l_unlikely(sequentialJumps == PLUTO_ILP_MAX_ITERATIONS)) {
+        if (l_unlikely(sequentialJumps >= pluto_ilp_limit(L))) {
+          sequentialJumps = 0;  /* the next loop gets the full limit again */
           PLUTO_ILP_ERROR;
           vmbreak;
         }
@@ -2289,7 +2291,8 @@ void luaV_execute (lua_State *L, CallInfo *ci) {
       vmcase(OP_TAILCALL) {
 #ifdef PLUTO_ILP_ENABLE
         ++sequentialTailCalls;
-        if (l_unlikely(sequentialTailCalls == PLUTO_ILP_MAX_ITERATIONS)) {
+        if (l_unlikely(sequentialTailCalls >= pluto_ilp_limit(L))) {
+          sequentialTailCalls = 0;  /* the next tail calls get the full limit again */
           PLUTO_ILP_ERROR;
           vmbreak;
         }
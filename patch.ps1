@@ -1,3 +1,24 @@
 # Unfortunately, the Pluto author refused to change the hook ABIs to the C ABI (with a fair reason),
 # which means we'll have to patch it ourselves. See: https://github.com/PlutoLang/Pluto/issues/1107
 git apply --ignore-whitespace --whitespace=fix patches/hook_c_abi.patch
+
+# Makes the ILP limits configurable per state at runtime (Build::opt_ilp_runtime), which needs fields in the global state.
+git apply --ignore-whitespace --whitespace=fix patches/runtime_ilp.patch
+
+# Lets the ETL deadline start unset when it's configured per state at runtime (Build::opt_etl_runtime).
+git apply --ignore-whitespace --whitespace=fix patches/runtime_etl.patch
+
+# Makes the memory limit configurable per state at runtime and tracks peak usage (Build::opt_memory_runtime).
+git apply --ignore-whitespace --whitespace=fix patches/runtime_memory.patch
+
+# Lets the socket library be left out of the preloaded libraries (Build::opt_no_socketlib).
+git apply --ignore-whitespace --whitespace=fix patches/no_socketlib.patch
+
+# Lets the other libraries be left out of the preloaded libraries too (Build::libraries).
+git apply --ignore-whitespace --whitespace=fix patches/library_subset.patch
+
+# Lets the state be closed without Soup's scheduler when the http library is left out (Build::libraries).
+git apply --ignore-whitespace --whitespace=fix patches/no_httplib_scheduler.patch
+
+# Lets the VM dump opcode lists be set from the build (Build::opt_vmdump_ignore, Build::opt_vmdump_allow).
+git apply --ignore-whitespace --whitespace=fix patches/vmdump_lists.patch
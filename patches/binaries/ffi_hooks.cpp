@@ -0,0 +1,14 @@
+#include "lua.hpp"
+
+// The pluto and plutoc binaries built by `Build::compile_binaries` don't contain pluto-ffi,
+// so hooks routed to it allow everything, just like pluto-ffi does when no hook is set.
+
+#ifdef PLUTOW_FFI_HOOKS
+extern "C" bool plutoffi_load_hook(lua_State*, void*, const char*) { return true; }
+extern "C" bool plutoffi_loadfile_hook(lua_State*, void*, const char*) { return true; }
+extern "C" bool plutoffi_loadclib_hook(lua_State*, void*, const char*) { return true; }
+extern "C" bool plutoffi_read_file_hook(lua_State*, void*, const char*) { return true; }
+extern "C" bool plutoffi_write_file_hook(lua_State*, void*, const char*) { return true; }
+extern "C" bool plutoffi_http_request_hook(lua_State*, void*, const char*) { return true; }
+extern "C" bool plutoffi_ffi_call_hook(lua_State*, void*, void*) { return true; }
+#endif
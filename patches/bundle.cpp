@@ -0,0 +1,79 @@
+#define LUA_LIB
+
+#include <cstdint>
+#include <string>
+
+#include "lua.hpp"
+#include "vendor/Soup/soup/deflate.hpp"
+
+// Script bundles packed by `Build::bundle_scripts` and installed by pluto-ffi's `install_bundle!`.
+// A bundle is zlib-compressed. For each module, it contains the module name, the script path and the code,
+// each prefixed by its length as a little-endian u32.
+
+static bool plutow_bundle_read(const std::string& data, size_t& pos, std::string& out) {
+    if (data.size() - pos < 4) return false;
+    const auto* p = reinterpret_cast<const unsigned char*>(data.data() + pos);
+    const size_t len = p[0] | (p[1] << 8) | (p[2] << 16) | (uint32_t(p[3]) << 24);
+    pos += 4;
+    if (data.size() - pos < len) return false;
+    out = data.substr(pos, len);
+    pos += len;
+    return true;
+}
+
+// Upvalues: the code and the path of each module by name.
+static int plutow_bundle_searcher(lua_State* L) {
+    const char* name = luaL_checkstring(L, 1);
+    if (lua_getfield(L, lua_upvalueindex(1), name) != LUA_TSTRING) {
+        lua_pushfstring(L, "no module '%s' in the script bundle", name);
+        return 1;
+    }
+    size_t size;
+    const char* code = lua_tolstring(L, -1, &size);
+    lua_getfield(L, lua_upvalueindex(2), name);
+    const char* path = lua_tostring(L, -1);
+    lua_pushfstring(L, "@%s", path);
+    if (luaL_loadbufferx(L, code, size, lua_tostring(L, -1), nullptr) != LUA_OK) {
+        return luaL_error(L, "error loading module '%s' from the script bundle:\n\t%s", name, lua_tostring(L, -1));
+    }
+    lua_pushvalue(L, -3);  // the path is passed to the module, like for files
+    return 2;
+}
+
+LUALIB_API void plutow_install_bundle(lua_State* L, const void* data, size_t size) {
+    const auto result = soup::deflate::decompress(data, size);
+    if (!result.checksum_present || result.checksum_mismatch) {
+        luaL_error(L, "corrupt script bundle");
+    }
+
+    lua_newtable(L);  // code
+    lua_newtable(L);  // paths
+    size_t pos = 0;
+    std::string name, path, code;
+    while (pos != result.decompressed.size()) {
+        if (!plutow_bundle_read(result.decompressed, pos, name)
+            || !plutow_bundle_read(result.decompressed, pos, path)
+            || !plutow_bundle_read(result.decompressed, pos, code)) {
+            luaL_error(L, "corrupt script bundle");
+        }
+        lua_pushlstring(L, code.data(), code.size());
+        lua_setfield(L, -3, name.c_str());
+        lua_pushlstring(L, path.data(), path.size());
+        lua_setfield(L, -2, name.c_str());
+    }
+    lua_pushcclosure(L, plutow_bundle_searcher, 2);
+
+    // insert the searcher after the preload searcher, so the bundle takes precedence over the filesystem
+    luaL_getsubtable(L, LUA_REGISTRYINDEX, LUA_LOADED_TABLE);
+    if (lua_getfield(L, -1, LUA_LOADLIBNAME) != LUA_TTABLE
+        || lua_getfield(L, -1, "searchers") != LUA_TTABLE) {
+        luaL_error(L, "the package library must be loaded to install a script bundle");
+    }
+    for (lua_Integer i = luaL_len(L, -1); i >= 2; --i) {
+        lua_rawgeti(L, -1, i);
+        lua_rawseti(L, -2, i + 1);
+    }
+    lua_pushvalue(L, -4);
+    lua_rawseti(L, -2, 2);
+    lua_pop(L, 4);
+}
@@ -0,0 +1,99 @@
+#define LUA_LIB
+
+#include "lua.hpp"
+
+// Per-state hook policies, attached to the registry by pluto-ffi's `set_policy!`.
+// The policy itself is owned by Rust; we only keep it alive until the state collects it.
+
+static const char plutow_policy_key = 0;
+
+struct PlutowPolicy {
+    void* policy;
+    void (*drop)(void*);
+};
+
+static int plutow_policy_gc(lua_State* L) {
+    auto p = static_cast<PlutowPolicy*>(lua_touserdata(L, 1));
+    if (p->policy != nullptr) {
+        p->drop(p->policy);
+        p->policy = nullptr;
+    }
+    return 0;
+}
+
+// Attaches an empty policy userdata and returns it. The policy is only stored once this succeeded,
+// so if it raises an error (e.g. out of memory), nothing but the caller owns the policy.
+static int plutow_attach_policy(lua_State* L) {
+    auto p = static_cast<PlutowPolicy*>(lua_newuserdatauv(L, sizeof(PlutowPolicy), 0));
+    p->policy = nullptr;
+    lua_createtable(L, 0, 1);
+    lua_pushcfunction(L, plutow_policy_gc);
+    lua_setfield(L, -2, "__gc");
+    lua_setmetatable(L, -2);
+    lua_pushvalue(L, -1);
+    // the previous policy (if any) is dropped once its userdata is collected
+    lua_rawsetp(L, LUA_REGISTRYINDEX, &plutow_policy_key);
+    return 1;
+}
+
+LUALIB_API void plutow_set_policy(lua_State* L, void* policy, void (*drop)(void*)) {
+    lua_pushcfunction(L, plutow_attach_policy);
+    if (lua_pcall(L, 0, 1, 0) != LUA_OK) {
+        drop(policy);
+        lua_error(L);
+    }
+    auto p = static_cast<PlutowPolicy*>(lua_touserdata(L, -1));
+    p->policy = policy;
+    p->drop = drop;
+    lua_pop(L, 1);
+}
+
+LUALIB_API void* plutow_get_policy(lua_State* L) {
+    void* policy = nullptr;
+    if (lua_rawgetp(L, LUA_REGISTRYINDEX, &plutow_policy_key) == LUA_TUSERDATA) {
+        policy = static_cast<PlutowPolicy*>(lua_touserdata(L, -1))->policy;
+    }
+    lua_pop(L, 1);
+    return policy;
+}
+
+#ifdef PLUTOW_FFI_HOOKS
+// Hooks routed to pluto-ffi by `Build::opt_ffi_hook`. These forward to the trampolines
+// exported by pluto-ffi, together with the policy of the calling state.
+
+extern "C" bool plutoffi_load_hook(lua_State* L, void* policy, const char* code);
+extern "C" bool plutoffi_loadfile_hook(lua_State* L, void* policy, const char* filename);
+extern "C" bool plutoffi_loadclib_hook(lua_State* L, void* policy, const char* path);
+extern "C" bool plutoffi_read_file_hook(lua_State* L, void* policy, const char* path);
+extern "C" bool plutoffi_write_file_hook(lua_State* L, void* policy, const char* path);
+extern "C" bool plutoffi_http_request_hook(lua_State* L, void* policy, const char* url);
+extern "C" bool plutoffi_ffi_call_hook(lua_State* L, void* policy, void* addr);
+
+extern "C" bool plutow_load_hook(lua_State* L, const char* code) {
+    return plutoffi_load_hook(L, plutow_get_policy(L), code);
+}
+
+extern "C" bool plutow_loadfile_hook(lua_State* L, const char* filename) {
+    return plutoffi_loadfile_hook(L, plutow_get_policy(L), filename);
+}
+
+extern "C" bool plutow_loadclib_hook(lua_State* L, const char* path) {
+    return plutoffi_loadclib_hook(L, plutow_get_policy(L), path);
+}
+
+extern "C" bool plutow_read_file_hook(lua_State* L, const char* path) {
+    return plutoffi_read_file_hook(L, plutow_get_policy(L), path);
+}
+
+extern "C" bool plutow_write_file_hook(lua_State* L, const char* path) {
+    return plutoffi_write_file_hook(L, plutow_get_policy(L), path);
+}
+
+extern "C" bool plutow_http_request_hook(lua_State* L, const char* url) {
+    return plutoffi_http_request_hook(L, plutow_get_policy(L), url);
+}
+
+extern "C" bool plutow_ffi_call_hook(lua_State* L, void* addr) {
+    return plutoffi_ffi_call_hook(L, plutow_get_policy(L), addr);
+}
+#endif
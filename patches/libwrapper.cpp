@@ -1,4 +1,7 @@
+#define LUA_LIB
+
 #include "lua.hpp"
+#include <cstring>
 
 bool plutow_wants_lib(const char** libs, int num_libs, const char* lib) {
     if (libs == nullptr || num_libs == 0) return true;
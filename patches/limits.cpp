@@ -0,0 +1,117 @@
+#define LUA_LIB
+
+#include <chrono>
+#include <cstdint>
+#include <limits>
+
+#include "lua.hpp"
+#include "lstate.h"
+
+// Runtime configuration of Pluto's sandbox limits, used by pluto-ffi's `set_*!` and `get_*!` macros.
+// These are always compiled in, but report whether the corresponding runtime option is enabled.
+
+LUALIB_API bool plutow_ilp_set(lua_State* L, int max_iterations, bool silent_break) {
+#ifdef PLUTOW_ILP_RUNTIME
+    G(L)->ilp_max_iterations = max_iterations;
+    G(L)->ilp_silent_break = silent_break;
+    return true;
+#else
+    (void)L; (void)max_iterations; (void)silent_break;
+    return false;
+#endif
+}
+
+LUALIB_API bool plutow_ilp_get(lua_State* L, int* max_iterations, bool* silent_break) {
+#ifdef PLUTOW_ILP_RUNTIME
+    *max_iterations = G(L)->ilp_max_iterations;
+    *silent_break = G(L)->ilp_silent_break;
+    return true;
+#else
+    (void)L; (void)max_iterations; (void)silent_break;
+    return false;
+#endif
+}
+
+#ifdef PLUTOW_ETL_RUNTIME
+static std::time_t plutow_etl_now() {
+    return std::chrono::duration_cast<std::chrono::nanoseconds>(std::chrono::steady_clock::now().time_since_epoch()).count();
+}
+#endif
+
+// Sets the deadline to `nanos` from now. A negative value clears the deadline.
+LUALIB_API bool plutow_etl_set(lua_State* L, int64_t nanos) {
+#ifdef PLUTOW_ETL_RUNTIME
+    constexpr auto none = std::numeric_limits<std::time_t>::max();
+    const auto now = plutow_etl_now();
+    G(L)->deadline = (nanos < 0 || nanos > none - now) ? none : now + nanos;
+    return true;
+#else
+    (void)L; (void)nanos;
+    return false;
+#endif
+}
+
+// Moves the deadline `nanos` further into the future. States without a deadline are unaffected.
+LUALIB_API bool plutow_etl_extend(lua_State* L, int64_t nanos) {
+#ifdef PLUTOW_ETL_RUNTIME
+    constexpr auto none = std::numeric_limits<std::time_t>::max();
+    auto& deadline = G(L)->deadline;
+    deadline = (nanos > none - deadline) ? none : deadline + nanos;
+    return true;
+#else
+    (void)L; (void)nanos;
+    return false;
+#endif
+}
+
+// Returns the nanoseconds left until the deadline (0 if it has passed), or -1 if there is no deadline.
+LUALIB_API bool plutow_etl_get(lua_State* L, int64_t* remaining) {
+#ifdef PLUTOW_ETL_RUNTIME
+    const auto deadline = G(L)->deadline;
+    if (deadline == std::numeric_limits<std::time_t>::max()) {
+        *remaining = -1;
+    }
+    else {
+        const auto now = plutow_etl_now();
+        *remaining = deadline > now ? deadline - now : 0;
+    }
+    return true;
+#else
+    (void)L; (void)remaining;
+    return false;
+#endif
+}
+
+// Sets the memory limit in bytes. 0 removes the limit.
+LUALIB_API bool plutow_memory_set_limit(lua_State* L, size_t limit) {
+#ifdef PLUTOW_MEMORY_RUNTIME
+    G(L)->memory_limit = limit;
+    return true;
+#else
+    (void)L; (void)limit;
+    return false;
+#endif
+}
+
+LUALIB_API bool plutow_memory_get(lua_State* L, size_t* limit, size_t* current, size_t* peak) {
+#ifdef PLUTOW_MEMORY_RUNTIME
+    *limit = G(L)->memory_limit;
+    *current = gettotalbytes(G(L));
+    *peak = G(L)->memory_peak;
+    return true;
+#else
+    (void)L; (void)limit; (void)current; (void)peak;
+    return false;
+#endif
+}
+
+// Resets the peak memory usage to the current memory usage.
+LUALIB_API bool plutow_memory_reset_peak(lua_State* L) {
+#ifdef PLUTOW_MEMORY_RUNTIME
+    G(L)->memory_peak = gettotalbytes(G(L));
+    return true;
+#else
+    (void)L;
+    return false;
+#endif
+}
@@ -0,0 +1,35 @@
+//! Loading modules from a script bundle packed by `Build::bundle_scripts`.
+//!
+//! The bundle is compiled into your binary and served by a `require` searcher,
+//! so multi-file script projects work even if Pluto is built without filesystem access:
+//!
+//! ```rust,ignore
+//! const BUNDLE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/pluto_bundle.bin"));
+//!
+//! pluto_ffi::install_bundle!(&lua, BUNDLE)?;
+//! lua.load(r#"require("util.json")"#).exec()?;
+//! ```
+
+/// Installs a `require` searcher serving the modules of the given script bundle into the given Lua state.
+/// The bundle takes precedence over the filesystem, but not over `package.preload`.
+///
+/// Returns an error if the bundle is corrupt or the `package` library isn't loaded.
+#[macro_export]
+macro_rules! install_bundle {
+    ($lua:expr, $bundle:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_install_bundle(
+                    L: *mut mlua::ffi::lua_State,
+                    data: *const std::os::raw::c_void,
+                    size: usize,
+                );
+            }
+            let bundle: &[u8] = $bundle;
+            $lua.exec_raw::<()>((), |state| {
+                plutow_install_bundle(state, bundle.as_ptr().cast(), bundle.len())
+            })
+        }
+    };
+}
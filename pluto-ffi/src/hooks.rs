@@ -0,0 +1,250 @@
+//! Typed content moderation hooks.
+//!
+//! Route the hooks you want to `pluto-ffi` in your build script using `Build::opt_ffi_hook` (or `Build::opt_ffi_hooks`),
+//! then install their implementations at runtime, either globally:
+//!
+//! ```rust,no_run
+//! pluto_ffi::hooks::set_load_hook(|code: &str| !code.contains("os.exit"));
+//! pluto_ffi::hooks::set_http_request_hook(|url: &str| url.starts_with("https://example.com/"));
+//! ```
+//!
+//! Or per state, by attaching a [`Policy`] using [`set_policy!`][crate::set_policy]:
+//!
+//! ```rust,ignore
+//! let untrusted = mlua::Lua::new();
+//! pluto_ffi::set_policy!(&untrusted, Policy::new().load(|_: &str| false))?;
+//! ```
+//!
+//! Hooks that aren't set on a state's policy fall back to the global ones.
+//! A routed hook without any implementation allows everything.
+//! If an implementation panics, the operation is denied.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, RwLock};
+
+macro_rules! define_hooks {
+    ($(
+        $(#[$meta:meta])*
+        $hook:ident($arg:ident: $ty:ty) => $field:ident, $setter:ident;
+    )*) => {
+        $(
+            $(#[$meta])*
+            ///
+            /// This is implemented for all closures with a matching signature.
+            pub trait $hook: Send + Sync + 'static {
+                /// Returns `true` if the operation is allowed. If it returns `false`, a Lua error is raised.
+                fn allow(&self, $arg: $ty) -> bool;
+            }
+
+            impl<F> $hook for F
+            where
+                F: Fn($ty) -> bool + Send + Sync + 'static,
+            {
+                fn allow(&self, $arg: $ty) -> bool {
+                    self($arg)
+                }
+            }
+
+            #[doc = concat!("Installs the global [`", stringify!($hook), "`], replacing the previous one.")]
+            pub fn $setter(hook: impl $hook) {
+                GLOBAL.write().unwrap_or_else(|e| e.into_inner()).$field = Some(Arc::new(hook));
+            }
+        )*
+
+        /// A set of hooks that can be attached to a state using [`set_policy!`][crate::set_policy].
+        #[derive(Default)]
+        pub struct Policy {
+            $($field: Option<Arc<dyn $hook>>,)*
+        }
+
+        impl Policy {
+            /// Creates a policy without any hooks, which defers everything to the global hooks.
+            pub const fn new() -> Self {
+                Self { $($field: None,)* }
+            }
+
+            $(
+                #[doc = concat!("Sets the [`", stringify!($hook), "`] of this policy.")]
+                pub fn $field(mut self, hook: impl $hook) -> Self {
+                    self.$field = Some(Arc::new(hook));
+                    self
+                }
+            )*
+        }
+    };
+}
+
+define_hooks! {
+    /// Moderates code passed to `load`. Routed using `Hook::Load`.
+    LoadHook(code: &str) => load, set_load_hook;
+    /// Moderates files loaded by `require` and `dofile`. Routed using `Hook::LoadFile`.
+    LoadFileHook(filename: &str) => loadfile, set_loadfile_hook;
+    /// Moderates C libraries loaded by `require` and `package.loadlib`. Routed using `Hook::LoadClib`.
+    LoadClibHook(path: &str) => loadclib, set_loadclib_hook;
+    /// Moderates any attempt to read a file's contents or metadata. Routed using `Hook::ReadFile`.
+    ReadFileHook(path: &str) => read_file, set_read_file_hook;
+    /// Moderates any attempt to write a file's contents or metadata. Routed using `Hook::WriteFile`.
+    WriteFileHook(path: &str) => write_file, set_write_file_hook;
+    /// Moderates HTTP requests. Routed using `Hook::HttpRequest`.
+    HttpRequestHook(url: &str) => http_request, set_http_request_hook;
+    /// Moderates calls to foreign functions, given the address of the function. Routed using `Hook::FfiCall`.
+    FfiCallHook(addr: *mut c_void) => ffi_call, set_ffi_call_hook;
+}
+
+static GLOBAL: RwLock<Policy> = RwLock::new(Policy::new());
+
+/// Removes all global hooks, which makes every routed hook allow everything again
+/// (unless a state has its own policy).
+pub fn clear_hooks() {
+    *GLOBAL.write().unwrap_or_else(|e| e.into_inner()) = Policy::new();
+}
+
+/// Attaches a [`Policy`][crate::hooks::Policy] to the given Lua state, replacing the previous one.
+/// The policy is dropped when the state is closed or its policy is replaced.
+#[macro_export]
+macro_rules! set_policy {
+    ($lua:expr, $policy:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_set_policy(
+                    L: *mut mlua::ffi::lua_State,
+                    policy: *mut std::ffi::c_void,
+                    drop: unsafe extern "C" fn(*mut std::ffi::c_void),
+                );
+            }
+            let policy: $crate::hooks::Policy = $policy;
+            let policy = Box::new(policy);
+            // only released to the state once it runs, so the policy is dropped if it never does
+            $lua.exec_raw::<()>((), move |state| {
+                let policy = Box::into_raw(policy).cast();
+                plutow_set_policy(state, policy, $crate::hooks::drop_policy)
+            })
+        }
+    };
+}
+
+#[doc(hidden)]
+pub unsafe extern "C" fn drop_policy(policy: *mut c_void) {
+    drop(Box::from_raw(policy.cast::<Policy>()));
+}
+
+unsafe fn dispatch<H: ?Sized>(
+    policy: *const c_void,
+    hook: impl Fn(&Policy) -> Option<&Arc<H>>,
+    allow: impl FnOnce(&H) -> bool,
+) -> bool {
+    let allow = |hook: &H| catch_unwind(AssertUnwindSafe(|| allow(hook))).unwrap_or(false);
+    if let Some(hook) = policy.cast::<Policy>().as_ref().and_then(&hook) {
+        return allow(hook);
+    }
+    // the lock isn't held while the hook runs, so it can replace the global hooks
+    let global = hook(&GLOBAL.read().unwrap_or_else(|e| e.into_inner())).cloned();
+    global.is_none_or(|hook| allow(&hook))
+}
+
+unsafe fn dispatch_str<H: ?Sized>(
+    policy: *const c_void,
+    s: *const c_char,
+    hook: impl Fn(&Policy) -> Option<&Arc<H>>,
+    allow: impl FnOnce(&H, &str) -> bool,
+) -> bool {
+    let s = CStr::from_ptr(s).to_string_lossy();
+    dispatch(policy, hook, |hook| allow(hook, &s))
+}
+
+// Trampolines called by the hooks in patches/hooks.cpp, which pass along the policy of the calling state.
+
+#[no_mangle]
+unsafe extern "C" fn plutoffi_load_hook(
+    _: *mut c_void,
+    policy: *const c_void,
+    code: *const c_char,
+) -> bool {
+    dispatch_str(
+        policy,
+        code,
+        |p| p.load.as_ref(),
+        |hook, code| hook.allow(code),
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn plutoffi_loadfile_hook(
+    _: *mut c_void,
+    policy: *const c_void,
+    filename: *const c_char,
+) -> bool {
+    dispatch_str(
+        policy,
+        filename,
+        |p| p.loadfile.as_ref(),
+        |hook, filename| hook.allow(filename),
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn plutoffi_loadclib_hook(
+    _: *mut c_void,
+    policy: *const c_void,
+    path: *const c_char,
+) -> bool {
+    dispatch_str(
+        policy,
+        path,
+        |p| p.loadclib.as_ref(),
+        |hook, path| hook.allow(path),
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn plutoffi_read_file_hook(
+    _: *mut c_void,
+    policy: *const c_void,
+    path: *const c_char,
+) -> bool {
+    dispatch_str(
+        policy,
+        path,
+        |p| p.read_file.as_ref(),
+        |hook, path| hook.allow(path),
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn plutoffi_write_file_hook(
+    _: *mut c_void,
+    policy: *const c_void,
+    path: *const c_char,
+) -> bool {
+    dispatch_str(
+        policy,
+        path,
+        |p| p.write_file.as_ref(),
+        |hook, path| hook.allow(path),
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn plutoffi_http_request_hook(
+    _: *mut c_void,
+    policy: *const c_void,
+    url: *const c_char,
+) -> bool {
+    dispatch_str(
+        policy,
+        url,
+        |p| p.http_request.as_ref(),
+        |hook, url| hook.allow(url),
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn plutoffi_ffi_call_hook(
+    _: *mut c_void,
+    policy: *const c_void,
+    addr: *mut c_void,
+) -> bool {
+    dispatch(policy, |p| p.ffi_call.as_ref(), |hook| hook.allow(addr))
+}
@@ -1,3 +1,7 @@
+pub mod bundle;
+pub mod hooks;
+pub mod limits;
+
 // Due to the way linking works in Rust (dependencies before build script),
 // we have to use a macro to do all of this inside of the crate itself.
 
@@ -29,47 +33,128 @@ macro_rules! load_libraries {
 }
 
 /// PlutoLibrary defines the available Pluto libraries that can be loaded.
+/// Variants only exist for the libraries enabled by the `lib-*` features, which must match those of pluto-build.
 pub enum PlutoLibrary {
+    #[cfg(feature = "lib-crypto")]
     Crypto,
+    #[cfg(feature = "lib-json")]
     Json,
+    #[cfg(feature = "lib-base32")]
     Base32,
+    #[cfg(feature = "lib-base64")]
     Base64,
+    #[cfg(feature = "lib-assert")]
     Assert,
+    #[cfg(feature = "lib-vector3")]
     Vector3,
+    #[cfg(feature = "lib-url")]
     Url,
+    #[cfg(feature = "lib-star")]
     Star,
+    #[cfg(feature = "lib-cat")]
     Cat,
+    #[cfg(feature = "lib-http")]
     Http,
+    #[cfg(feature = "lib-scheduler")]
     Scheduler,
+    #[cfg(feature = "lib-socket")]
     Socket,
+    #[cfg(feature = "lib-bigint")]
     BigInt,
+    #[cfg(feature = "lib-xml")]
     Xml,
+    #[cfg(feature = "lib-regex")]
     Regex,
+    #[cfg(feature = "lib-ffi")]
     FFI,
+    #[cfg(feature = "lib-canvas")]
     Canvas,
 }
 
 impl PlutoLibrary {
     pub fn ffi_name(&self) -> std::ffi::CString {
-        std::ffi::CString::new(match self {
+        std::ffi::CString::new(self.name()).unwrap()
+    }
+
+    #[cfg(any(
+        feature = "lib-crypto",
+        feature = "lib-json",
+        feature = "lib-base32",
+        feature = "lib-base64",
+        feature = "lib-assert",
+        feature = "lib-vector3",
+        feature = "lib-url",
+        feature = "lib-star",
+        feature = "lib-cat",
+        feature = "lib-http",
+        feature = "lib-scheduler",
+        feature = "lib-socket",
+        feature = "lib-bigint",
+        feature = "lib-xml",
+        feature = "lib-regex",
+        feature = "lib-ffi",
+        feature = "lib-canvas",
+    ))]
+    fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "lib-crypto")]
             Self::Crypto => "crypto",
+            #[cfg(feature = "lib-json")]
             Self::Json => "json",
+            #[cfg(feature = "lib-base32")]
             Self::Base32 => "base32",
+            #[cfg(feature = "lib-base64")]
             Self::Base64 => "base64",
+            #[cfg(feature = "lib-assert")]
             Self::Assert => "assert",
+            #[cfg(feature = "lib-vector3")]
             Self::Vector3 => "vector3",
+            #[cfg(feature = "lib-url")]
             Self::Url => "url",
+            #[cfg(feature = "lib-star")]
             Self::Star => "*",
+            #[cfg(feature = "lib-cat")]
             Self::Cat => "cat",
+            #[cfg(feature = "lib-http")]
             Self::Http => "http",
+            #[cfg(feature = "lib-scheduler")]
             Self::Scheduler => "scheduler",
+            #[cfg(feature = "lib-socket")]
             Self::Socket => "socket",
+            #[cfg(feature = "lib-bigint")]
             Self::BigInt => "bigint",
+            #[cfg(feature = "lib-xml")]
             Self::Xml => "xml",
+            #[cfg(feature = "lib-regex")]
             Self::Regex => "regex",
+            #[cfg(feature = "lib-ffi")]
             Self::FFI => "ffi",
+            #[cfg(feature = "lib-canvas")]
             Self::Canvas => "canvas",
-        })
-        .unwrap()
+        }
+    }
+
+    /// Without any library, there are no variants to name.
+    #[cfg(not(any(
+        feature = "lib-crypto",
+        feature = "lib-json",
+        feature = "lib-base32",
+        feature = "lib-base64",
+        feature = "lib-assert",
+        feature = "lib-vector3",
+        feature = "lib-url",
+        feature = "lib-star",
+        feature = "lib-cat",
+        feature = "lib-http",
+        feature = "lib-scheduler",
+        feature = "lib-socket",
+        feature = "lib-bigint",
+        feature = "lib-xml",
+        feature = "lib-regex",
+        feature = "lib-ffi",
+        feature = "lib-canvas",
+    )))]
+    fn name(&self) -> &'static str {
+        match *self {}
     }
 }
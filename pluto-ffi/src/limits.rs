@@ -0,0 +1,246 @@
+//! Runtime configuration of Pluto's sandbox limits.
+//!
+//! Each limit must be made runtime-configurable in your build script first, e.g. using `Build::opt_ilp_runtime`.
+//! The `set_*!` and `get_*!` macros return an error if Pluto was built without it.
+
+/// Infinite loop prevention settings of a state. Requires `Build::opt_ilp_runtime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ilp {
+    /// The maximum amount of backward jumps permitted in a singular loop block. Must be at least 1.
+    pub max_iterations: u32,
+    /// If `true`, Pluto won't throw an error and instead just break out of the loop.
+    pub silent_break: bool,
+}
+
+/// Sets the infinite loop prevention settings of the given Lua state.
+/// Requires `Build::opt_ilp_runtime`. Returns an error if `max_iterations` is 0, which would stop every loop.
+///
+/// ```rust,ignore
+/// pluto_ffi::set_ilp!(&lua, Ilp { max_iterations: 10_000, silent_break: false })?;
+/// ```
+#[macro_export]
+macro_rules! set_ilp {
+    ($lua:expr, $ilp:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_ilp_set(
+                    L: *mut mlua::ffi::lua_State,
+                    max_iterations: std::os::raw::c_int,
+                    silent_break: bool,
+                ) -> bool;
+            }
+            let ilp: $crate::limits::Ilp = $ilp;
+            if ilp.max_iterations == 0 {
+                Err(mlua::Error::runtime(
+                    "`Ilp::max_iterations` must be at least 1",
+                ))
+            } else {
+                let max_iterations = ilp.max_iterations.min(i32::MAX as u32) as _;
+                let mut supported = false;
+                $lua.exec_raw::<()>((), |state| {
+                    supported = plutow_ilp_set(state, max_iterations, ilp.silent_break)
+                })
+                .and_then(|()| $crate::limits::supported!(supported, "opt_ilp_runtime"))
+            }
+        }
+    };
+}
+
+/// Returns the infinite loop prevention settings of the given Lua state.
+/// Requires `Build::opt_ilp_runtime`.
+#[macro_export]
+macro_rules! get_ilp {
+    ($lua:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_ilp_get(
+                    L: *mut mlua::ffi::lua_State,
+                    max_iterations: *mut std::os::raw::c_int,
+                    silent_break: *mut bool,
+                ) -> bool;
+            }
+            let (mut max_iterations, mut silent_break) = (0, false);
+            let mut supported = false;
+            $lua.exec_raw::<()>((), |state| {
+                supported = plutow_ilp_get(state, &mut max_iterations, &mut silent_break)
+            })
+            .and_then(|()| $crate::limits::supported!(supported, "opt_ilp_runtime"))
+            .map(|()| $crate::limits::Ilp {
+                max_iterations: max_iterations as u32,
+                silent_break,
+            })
+        }
+    };
+}
+
+/// Sets the execution deadline of the given Lua state to the given duration from now, or clears it if `None`.
+/// Once the deadline has passed, running code raises an error until the deadline is set again.
+/// Requires `Build::opt_etl_runtime`.
+///
+/// ```rust,ignore
+/// pluto_ffi::set_etl_deadline!(&lua, Some(Duration::from_millis(5)))?;
+/// callback.call::<()>(())?;
+/// ```
+#[macro_export]
+macro_rules! set_etl_deadline {
+    ($lua:expr, $timeout:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_etl_set(L: *mut mlua::ffi::lua_State, nanos: i64) -> bool;
+            }
+            let timeout: Option<std::time::Duration> = $timeout;
+            let nanos = timeout.map_or(-1, |t| t.as_nanos().min(i64::MAX as u128) as i64);
+            let mut supported = false;
+            $lua.exec_raw::<()>((), |state| supported = plutow_etl_set(state, nanos))
+                .and_then(|()| $crate::limits::supported!(supported, "opt_etl_runtime"))
+        }
+    };
+}
+
+/// Moves the execution deadline of the given Lua state further into the future.
+/// States without a deadline are unaffected. Requires `Build::opt_etl_runtime`.
+#[macro_export]
+macro_rules! extend_etl_deadline {
+    ($lua:expr, $duration:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_etl_extend(L: *mut mlua::ffi::lua_State, nanos: i64) -> bool;
+            }
+            let duration: std::time::Duration = $duration;
+            let nanos = duration.as_nanos().min(i64::MAX as u128) as i64;
+            let mut supported = false;
+            $lua.exec_raw::<()>((), |state| supported = plutow_etl_extend(state, nanos))
+                .and_then(|()| $crate::limits::supported!(supported, "opt_etl_runtime"))
+        }
+    };
+}
+
+/// Returns the time left until the execution deadline of the given Lua state, or `None` if it has no deadline.
+/// Returns [`Duration::ZERO`][std::time::Duration::ZERO] if the deadline has passed. Requires `Build::opt_etl_runtime`.
+#[macro_export]
+macro_rules! get_etl_deadline {
+    ($lua:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_etl_get(L: *mut mlua::ffi::lua_State, remaining: *mut i64) -> bool;
+            }
+            let mut remaining = 0;
+            let mut supported = false;
+            $lua.exec_raw::<()>((), |state| {
+                supported = plutow_etl_get(state, &mut remaining)
+            })
+            .and_then(|()| $crate::limits::supported!(supported, "opt_etl_runtime"))
+            .map(|()| {
+                u64::try_from(remaining)
+                    .ok()
+                    .map(std::time::Duration::from_nanos)
+            })
+        }
+    };
+}
+
+/// Memory usage of a state, in bytes. Requires `Build::opt_memory_runtime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The memory limit, or `None` if there is no limit.
+    pub limit: Option<usize>,
+    /// The amount of memory currently in use.
+    pub current: usize,
+    /// The highest amount of memory in use since the state was created or [`reset_memory_peak!`][crate::reset_memory_peak] was used.
+    pub peak: usize,
+}
+
+/// Sets the memory limit of the given Lua state in bytes, or removes it if `None`.
+/// Allocations that would exceed the limit fail with a memory error. Requires `Build::opt_memory_runtime`.
+///
+/// ```rust,ignore
+/// pluto_ffi::set_memory_limit!(&lua, Some(16_000_000))?;
+/// ```
+#[macro_export]
+macro_rules! set_memory_limit {
+    ($lua:expr, $limit:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_memory_set_limit(L: *mut mlua::ffi::lua_State, limit: usize) -> bool;
+            }
+            let limit: Option<usize> = $limit;
+            let limit = limit.map_or(0, |limit| limit.max(1));
+            let mut supported = false;
+            $lua.exec_raw::<()>((), |state| {
+                supported = plutow_memory_set_limit(state, limit)
+            })
+            .and_then(|()| $crate::limits::supported!(supported, "opt_memory_runtime"))
+        }
+    };
+}
+
+/// Returns the [`MemoryUsage`][crate::limits::MemoryUsage] of the given Lua state. Requires `Build::opt_memory_runtime`.
+#[macro_export]
+macro_rules! get_memory_usage {
+    ($lua:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_memory_get(
+                    L: *mut mlua::ffi::lua_State,
+                    limit: *mut usize,
+                    current: *mut usize,
+                    peak: *mut usize,
+                ) -> bool;
+            }
+            let (mut limit, mut current, mut peak) = (0, 0, 0);
+            let mut supported = false;
+            $lua.exec_raw::<()>((), |state| {
+                supported = plutow_memory_get(state, &mut limit, &mut current, &mut peak)
+            })
+            .and_then(|()| $crate::limits::supported!(supported, "opt_memory_runtime"))
+            .map(|()| $crate::limits::MemoryUsage {
+                limit: (limit != 0).then_some(limit),
+                current,
+                peak,
+            })
+        }
+    };
+}
+
+/// Resets the peak memory usage of the given Lua state to its current memory usage,
+/// e.g. to measure the peak of a single script. Requires `Build::opt_memory_runtime`.
+#[macro_export]
+macro_rules! reset_memory_peak {
+    ($lua:expr) => {
+        unsafe {
+            extern "C" {
+                #[allow(non_camel_case_types, non_snake_case)]
+                pub fn plutow_memory_reset_peak(L: *mut mlua::ffi::lua_State) -> bool;
+            }
+            let mut supported = false;
+            $lua.exec_raw::<()>((), |state| supported = plutow_memory_reset_peak(state))
+                .and_then(|()| $crate::limits::supported!(supported, "opt_memory_runtime"))
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __supported {
+    ($supported:expr, $option:literal) => {
+        if $supported {
+            Ok(())
+        } else {
+            Err(mlua::Error::runtime(concat!(
+                "Pluto was built without `Build::",
+                $option,
+                "`"
+            )))
+        }
+    };
+}
+
+#[doc(hidden)]
+pub use __supported as supported;
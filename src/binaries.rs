@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use crate::{build_soup_dependencies, cpp_stdlib, out_dir, report, run_linker};
+use crate::{soup_libs, Build, Error};
+
+/// The command-line tools built by [`Build::compile_binaries`].
+#[derive(Debug, Clone)]
+pub struct Binaries {
+    /// The interpreter and REPL, built from `lua.cpp`.
+    pub pluto: PathBuf,
+    /// The bytecode compiler, built from `luac.cpp`.
+    pub plutoc: PathBuf,
+}
+
+impl Build {
+    /// Builds the `pluto` and `plutoc` executables into `OUT_DIR`, with the same options as the embedded library.
+    ///
+    /// Panics if the options contradict each other or compilation fails. See [`try_compile_binaries`][Self::try_compile_binaries].
+    pub fn compile_binaries(&mut self) -> Binaries {
+        self.try_compile_binaries()
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Builds the `pluto` and `plutoc` executables into `OUT_DIR`, with the same options as the embedded library.
+    ///
+    /// This lets scripts be tested locally against the exact sandbox, ILP and ETL configuration of your Rust host.
+    /// If [`compile`][Self::compile] has already compiled Pluto statically, its libraries are reused.
+    /// The binaries are only built once per `Build`, later calls return the same ones.
+    /// The binaries are always linked statically and always use the vendored sources, even if a prebuilt Pluto is linked.
+    ///
+    /// NOTE: Hooks routed to `pluto-ffi` (see [`opt_ffi_hook`][Self::opt_ffi_hook]) allow everything in the binaries,
+    /// as if no hook was set.
+    pub fn try_compile_binaries(&mut self) -> Result<Binaries, Error> {
+        if let Some(binaries) = &self.binaries {
+            return Ok(binaries.clone());
+        }
+        self.apply_env_overrides()?;
+        report(self.config.validate())?;
+        let out_dir = out_dir()?;
+
+        let mut build = self.configured(&self.cc);
+        build.cargo_metadata(false);
+        for (name, value) in self.config.defines() {
+            build.define(name, value);
+        }
+
+        let mut pluto = build.clone();
+        self.add_sources(&mut pluto)?;
+        if !self.compiled.contains(&"soup") {
+            let mut soup = self.configured(&self.soup);
+            soup.cargo_metadata(false);
+            let soup_sources = self.soup_sources(&pluto)?;
+            build_soup_dependencies(&soup, &self.src_dir, &soup_sources, &self.target, false)?;
+            self.compiled.extend(soup_libs(&self.target));
+        }
+        if !self.compiled.contains(&"plutostatic") {
+            pluto.try_compile("plutostatic")?;
+            self.compiled.push("plutostatic");
+        }
+
+        let exe = if self.target.contains("windows") {
+            ".exe"
+        } else {
+            ""
+        };
+        let binaries = Binaries {
+            pluto: out_dir.join(format!("pluto{exe}")),
+            plutoc: out_dir.join(format!("plutoc{exe}")),
+        };
+        self.link_binary("lua", &binaries.pluto, &build)?;
+        self.link_binary("luac", &binaries.plutoc, &build)?;
+        self.binaries = Some(binaries.clone());
+        Ok(binaries)
+    }
+
+    fn link_binary(&self, main: &str, exe: &Path, build: &cc::Build) -> Result<(), Error> {
+        let out_dir = out_dir()?;
+        let stub = Path::new(env!("CARGO_MANIFEST_DIR")).join("patches/binaries/ffi_hooks.cpp");
+        let objects = build
+            .clone()
+            .file(self.src_dir.join(main).with_extension("cpp"))
+            .file(stub)
+            .try_compile_intermediates()?;
+        let compiler = build.try_get_compiler()?;
+        let mut cmd = compiler.to_command();
+        cmd.args(&objects);
+
+        let libs = std::iter::once(&"plutostatic").chain(soup_libs(&self.target));
+        if compiler.is_like_msvc() {
+            cmd.args(libs.map(|lib| out_dir.join(lib).with_extension("lib")))
+                .arg(format!("/Fe{}", exe.display()));
+        } else {
+            cmd.args(libs.map(|lib| out_dir.join(format!("lib{lib}.a"))))
+                .arg("-o")
+                .arg(exe);
+            for lib in self.link_libs {
+                cmd.arg(format!("-l{lib}"));
+            }
+            if let Some(stdlib) = cpp_stdlib() {
+                cmd.arg(format!("-l{stdlib}"));
+            }
+            if self.crt_static() {
+                cmd.arg("-static");
+            }
+        }
+        run_linker(&mut cmd)
+    }
+}
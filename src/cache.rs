@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::fingerprint::Fingerprint;
+
+/// A directory shared by build scripts to reuse Pluto libraries compiled by each other, keyed by their fingerprint.
+pub(crate) struct Cache {
+    dir: PathBuf,
+}
+
+/// The locked cache entry of a fingerprint. Concurrent build scripts wait until it's unlocked.
+pub(crate) struct Entry {
+    dir: PathBuf,
+    _lock: File,
+}
+
+impl Cache {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Locks the entry of `fingerprint`, waiting if another build script is compiling the same Pluto.
+    /// Returns `None` if the cache can't be used, in which case Pluto is compiled as if there was no cache.
+    pub(crate) fn lock(&self, fingerprint: &Fingerprint) -> Option<Entry> {
+        let lock = std::fs::create_dir_all(&self.dir)
+            .and_then(|()| File::create(self.dir.join(format!("{fingerprint}.lock"))))
+            .and_then(|file| file.lock().map(|()| file));
+        match lock {
+            Ok(lock) => Some(Entry {
+                dir: self.dir.join(fingerprint.to_string()),
+                _lock: lock,
+            }),
+            Err(err) => {
+                let dir = self.dir.display();
+                println!("cargo:warning=pluto-build: can't use the cache in {dir}: {err}");
+                None
+            }
+        }
+    }
+}
+
+impl Entry {
+    /// Copies the cached `files` to `out_dir`. Returns `false` if they haven't been cached yet.
+    pub(crate) fn restore(&self, files: &[String], out_dir: &Path) -> bool {
+        self.dir.is_dir()
+            && files
+                .iter()
+                .all(|file| std::fs::copy(self.dir.join(file), out_dir.join(file)).is_ok())
+    }
+
+    /// Caches the `files` compiled into `out_dir`.
+    pub(crate) fn store(&self, files: &[String], out_dir: &Path) {
+        // the entry is complete once it's renamed, in case a build script is interrupted while copying
+        let tmp = self.dir.with_extension("tmp");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let stored = std::fs::create_dir_all(&tmp)
+            .and_then(|()| {
+                files.iter().try_for_each(|file| {
+                    std::fs::copy(out_dir.join(file), tmp.join(file)).map(drop)
+                })
+            })
+            .and_then(|()| std::fs::rename(&tmp, &self.dir));
+        if let Err(err) = stored {
+            let dir = self.dir.display();
+            println!("cargo:warning=pluto-build: can't cache Pluto in {dir}: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn fingerprint(dir: &Path) -> Fingerprint {
+        let mut build = cc::Build::new();
+        build
+            .target("x86_64-unknown-linux-gnu")
+            .host("x86_64-unknown-linux-gnu")
+            .opt_level(0)
+            .cargo_metadata(false);
+        Fingerprint::new(&[&build], &[dir], &[]).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = crate::test_dir("cache-round-trip");
+        let (compiled, restored) = (dir.join("compiled"), dir.join("restored"));
+        std::fs::create_dir_all(&compiled).unwrap();
+        std::fs::create_dir_all(&restored).unwrap();
+        let files = ["libpluto.a".to_string(), "libsoup.a".to_string()];
+        for file in &files {
+            std::fs::write(compiled.join(file), file).unwrap();
+        }
+        let cache = Cache::new(dir.join("cache"));
+        let fingerprint = fingerprint(&compiled);
+
+        let entry = cache.lock(&fingerprint).unwrap();
+        assert!(!entry.restore(&files, &restored));
+        entry.store(&files, &compiled);
+
+        // another build script waits until the entry is stored and unlocked
+        let waiting = std::thread::scope(|scope| {
+            let waiting = scope.spawn(|| {
+                let entry = cache.lock(&fingerprint).unwrap();
+                entry.restore(&files, &restored)
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!waiting.is_finished());
+            drop(entry);
+            waiting.join().unwrap()
+        });
+        assert!(waiting);
+        for file in &files {
+            assert_eq!(std::fs::read_to_string(restored.join(file)).unwrap(), *file);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+
+use crate::Hook;
+
+/// The Pluto defines set using the `opt_*` methods of [`Build`][crate::Build] or `PLUTO_BUILD_DEFINES`.
+/// They're only passed to the compiler in `compile()`, after they've been validated.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Config {
+    defines: BTreeMap<String, Option<String>>,
+}
+
+/// A problem with a combination of options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Diagnostic {
+    /// The build works, but some options have no effect.
+    Warning(String),
+    /// The options contradict each other, so the build wouldn't behave as configured.
+    Error(String),
+}
+
+/// An option, identified by its define and the method of [`Build`][crate::Build] that sets it.
+#[derive(Debug, Clone, Copy)]
+struct Opt {
+    define: &'static str,
+    method: &'static str,
+}
+
+macro_rules! opts {
+    ($($name:ident = $define:literal, $method:ident;)*) => {
+        $(const $name: Opt = Opt { define: $define, method: stringify!($method) };)*
+    };
+}
+
+opts! {
+    FORCE_JUMPTABLE = "PLUTO_FORCE_JUMPTABLE", opt_force_jumptable;
+    ILP_ENABLED = "PLUTO_ILP_ENABLE", opt_ilp_enabled;
+    ILP_MAX_ITERATIONS = "PLUTO_ILP_MAX_ITERATIONS", opt_ilp_max_iterations;
+    ILP_HOOK_FUNCTION = "PLUTO_ILP_HOOK_FUNCTION", opt_ilp_hook_function;
+    ILP_SILENT_BREAK = "PLUTO_ILP_SILENT_BREAK", opt_ilp_silent_break;
+    ILP_ERROR = "PLUTO_ILP_ERROR", opt_ilp_error;
+    ETL_ENABLED = "PLUTO_ETL_ENABLE", opt_etl_enabled;
+    ETL_NANOS = "PLUTO_ETL_NANOS", opt_etl_nanos;
+    ETL_TIMESUP = "PLUTO_ETL_TIMESUP", opt_etl_timesup;
+    VMDUMP_ENABLED = "PLUTO_VMDUMP", opt_vmdump_enabled;
+    VMDUMP_IGNORE = "vmDumpIgnore", opt_vmdump_ignore;
+    VMDUMP_ALLOW = "vmDumpAllow", opt_vmdump_allow;
+    VMDUMP_WHITELIST = "PLUTO_VMDUMP_WHITELIST", opt_vmdump_whitelist;
+    VMDUMP_COND = "PLUTO_VMDUMP_COND", opt_vmdump_cond;
+    NO_BINARIES = "PLUTO_NO_BINARIES", opt_no_binaries;
+    NO_BINARIES_FAIL = "PLUTO_NO_BINARIES_FAIL", opt_no_binaries_fail;
+    LOADCLIB_HOOK = "PLUTO_LOADCLIB_HOOK", opt_loadclib_hook;
+    NO_FILESYSTEM = "PLUTO_NO_FILESYSTEM", opt_no_filesystem;
+    READ_FILE_HOOK = "PLUTO_READ_FILE_HOOK", opt_read_file_hook;
+    WRITE_FILE_HOOK = "PLUTO_WRITE_FILE_HOOK", opt_write_file_hook;
+    DISABLE_HTTP_COMPLETELY = "PLUTO_DISABLE_HTTP_COMPLETELY", opt_disable_http_completely;
+    HTTP_REQUEST_HOOK = "PLUTO_HTTP_REQUEST_HOOK", opt_http_request_hook;
+    ILP_RUNTIME = "PLUTOW_ILP_RUNTIME", opt_ilp_runtime;
+    ETL_RUNTIME = "PLUTOW_ETL_RUNTIME", opt_etl_runtime;
+    MEMORY_RUNTIME = "PLUTOW_MEMORY_RUNTIME", opt_memory_runtime;
+}
+
+/// Options that contradict each other.
+const CONFLICTS: &[(Opt, Opt)] = &[(ILP_ERROR, ILP_SILENT_BREAK)];
+
+/// Options that have no effect unless another option is set.
+const REQUIRES: &[(Opt, Opt)] = &[
+    (ILP_MAX_ITERATIONS, ILP_ENABLED),
+    (ILP_HOOK_FUNCTION, ILP_ENABLED),
+    (ILP_SILENT_BREAK, ILP_ENABLED),
+    (ILP_ERROR, ILP_ENABLED),
+    (ETL_NANOS, ETL_ENABLED),
+    (ETL_TIMESUP, ETL_ENABLED),
+    (VMDUMP_IGNORE, VMDUMP_ENABLED),
+    (VMDUMP_ALLOW, VMDUMP_ENABLED),
+    (VMDUMP_WHITELIST, VMDUMP_ENABLED),
+    (VMDUMP_COND, VMDUMP_ENABLED),
+    (VMDUMP_ALLOW, VMDUMP_WHITELIST),
+    (NO_BINARIES_FAIL, NO_BINARIES),
+];
+
+/// Options that have no effect if another option is set.
+const OVERRIDDEN_BY: &[(Opt, Opt)] = &[
+    (FORCE_JUMPTABLE, VMDUMP_ENABLED),
+    (FORCE_JUMPTABLE, ETL_ENABLED),
+    (VMDUMP_IGNORE, VMDUMP_WHITELIST),
+    (LOADCLIB_HOOK, NO_BINARIES),
+    (READ_FILE_HOOK, NO_FILESYSTEM),
+    (WRITE_FILE_HOOK, NO_FILESYSTEM),
+    (HTTP_REQUEST_HOOK, DISABLE_HTTP_COMPLETELY),
+];
+
+/// Options that need Pluto's sources to be patched by pluto-build.
+const PATCHED: &[Opt] = &[ILP_RUNTIME, ETL_RUNTIME, MEMORY_RUNTIME];
+
+/// The `plutow_*` functions `pluto-ffi` calls, which every Pluto built by pluto-build contains.
+const WRAPPER_FUNCTIONS: &[&str] = &[
+    "plutow_openlibs",
+    "plutow_set_policy",
+    "plutow_install_bundle",
+];
+
+/// The `plutow_*` functions `pluto-ffi` calls for an option, which older versions of pluto-build didn't have.
+const OPTION_FUNCTIONS: &[(Opt, &[&str])] = &[
+    (ILP_RUNTIME, &["plutow_ilp_set", "plutow_ilp_get"]),
+    (
+        ETL_RUNTIME,
+        &["plutow_etl_set", "plutow_etl_extend", "plutow_etl_get"],
+    ),
+    (
+        MEMORY_RUNTIME,
+        &[
+            "plutow_memory_set_limit",
+            "plutow_memory_get",
+            "plutow_memory_reset_peak",
+        ],
+    ),
+];
+
+impl Config {
+    pub(crate) fn define(&mut self, name: &str, value: Option<&str>) {
+        self.defines
+            .insert(name.to_owned(), value.map(str::to_owned));
+    }
+
+    pub(crate) fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains_key(name)
+    }
+
+    pub(crate) fn defines(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.defines
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+
+    /// Checks for conflicting, redundant or ineffective combinations of options.
+    pub(crate) fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for (a, b) in CONFLICTS {
+            if self.is_set(a) && self.is_set(b) {
+                diagnostics.push(Diagnostic::Error(format!(
+                    "`{}` and `{}` are mutually exclusive",
+                    a.method, b.method
+                )));
+            }
+        }
+        for (opt, required) in REQUIRES {
+            if self.is_set(opt) && !self.is_set(required) {
+                diagnostics.push(Diagnostic::Warning(format!(
+                    "`{}` has no effect without `{}`",
+                    opt.method, required.method
+                )));
+            }
+        }
+        for (opt, overriding) in OVERRIDDEN_BY {
+            if self.is_set(opt) && self.is_set(overriding) && !self.is_ffi_hook(opt) {
+                diagnostics.push(Diagnostic::Warning(format!(
+                    "`{}` has no effect with `{}`",
+                    opt.method, overriding.method
+                )));
+            }
+        }
+        diagnostics
+    }
+
+    /// Checks the options for linking a prebuilt Pluto. If it wasn't built by pluto-build (`patched` is `false`),
+    /// only the functions used by `pluto-ffi` are compiled with these options.
+    pub(crate) fn validate_prebuilt(&self, patched: bool) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for opt in PATCHED.iter().filter(|_| !patched) {
+            if self.is_set(opt) {
+                diagnostics.push(Diagnostic::Error(format!(
+                    "`{}` needs Pluto to be built by pluto-build, not a prebuilt Pluto",
+                    opt.method
+                )));
+            }
+        }
+        if self
+            .defines
+            .keys()
+            .any(|define| patched || !define.starts_with("PLUTOW_"))
+        {
+            diagnostics.push(Diagnostic::Warning(
+                "options don't affect a prebuilt Pluto, make sure they match how it was built"
+                    .into(),
+            ));
+        }
+        diagnostics
+    }
+
+    /// The `plutow_*` functions a prebuilt Pluto must contain for these options, see [`validate_prebuilt`][Self::validate_prebuilt].
+    pub(crate) fn wrapper_functions(&self) -> Vec<&'static str> {
+        let mut functions = WRAPPER_FUNCTIONS.to_vec();
+        for (opt, option_functions) in OPTION_FUNCTIONS {
+            if self.is_set(opt) {
+                functions.extend_from_slice(option_functions);
+            }
+        }
+        for hook in Hook::ALL {
+            let value = self.defines.get(hook.define()).and_then(Option::as_deref);
+            if value == Some(hook.trampoline()) {
+                functions.push(hook.trampoline());
+            }
+        }
+        functions
+    }
+
+    fn is_set(&self, opt: &Opt) -> bool {
+        self.is_defined(opt.define)
+    }
+
+    /// Hooks routed to `pluto-ffi` are typically routed all at once, so they aren't reported as redundant.
+    fn is_ffi_hook(&self, opt: &Opt) -> bool {
+        let value = self.defines.get(opt.define).and_then(Option::as_deref);
+        Hook::ALL
+            .iter()
+            .any(|hook| hook.define() == opt.define && value == Some(hook.trampoline()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with(defines: &[(&str, Option<&str>)]) -> Config {
+        let mut config = Config::default();
+        for (name, value) in defines {
+            config.define(name, *value);
+        }
+        config
+    }
+
+    #[test]
+    fn conflicting_options_are_errors() {
+        let config = with(&[
+            ("PLUTO_ILP_ENABLE", None),
+            ("PLUTO_ILP_ERROR", Some("abort()")),
+            ("PLUTO_ILP_SILENT_BREAK", None),
+        ]);
+        assert_eq!(
+            config.validate(),
+            [Diagnostic::Error(
+                "`opt_ilp_error` and `opt_ilp_silent_break` are mutually exclusive".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn options_without_their_requirement_are_warnings() {
+        let config = with(&[("PLUTO_ILP_MAX_ITERATIONS", Some("1000"))]);
+        assert_eq!(
+            config.validate(),
+            [Diagnostic::Warning(
+                "`opt_ilp_max_iterations` has no effect without `opt_ilp_enabled`".into()
+            )]
+        );
+
+        let config = with(&[
+            ("PLUTO_ILP_ENABLE", None),
+            ("PLUTO_ILP_MAX_ITERATIONS", Some("1000")),
+        ]);
+        assert_eq!(config.validate(), []);
+    }
+
+    #[test]
+    fn overridden_options_are_warnings() {
+        let config = with(&[
+            ("PLUTO_NO_FILESYSTEM", None),
+            ("PLUTO_READ_FILE_HOOK", Some("my_read_file_hook")),
+        ]);
+        assert_eq!(
+            config.validate(),
+            [Diagnostic::Warning(
+                "`opt_read_file_hook` has no effect with `opt_no_filesystem`".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn overridden_ffi_hooks_are_not_reported() {
+        let config = with(&[
+            ("PLUTO_NO_FILESYSTEM", None),
+            ("PLUTO_READ_FILE_HOOK", Some(Hook::ReadFile.trampoline())),
+        ]);
+        assert_eq!(config.validate(), []);
+    }
+
+    #[test]
+    fn wrapper_functions_follow_the_options() {
+        let config = with(&[
+            ("PLUTOW_ILP_RUNTIME", None),
+            ("PLUTO_LOAD_HOOK", Some(Hook::Load.trampoline())),
+            ("PLUTO_READ_FILE_HOOK", Some("my_read_file_hook")),
+        ]);
+        let functions = config.wrapper_functions();
+        assert!(functions.contains(&"plutow_set_policy"));
+        assert!(functions.contains(&"plutow_ilp_set"));
+        assert!(functions.contains(&"plutow_load_hook"));
+        assert!(!functions.contains(&"plutow_etl_set"));
+        assert!(!functions.contains(&"plutow_read_file_hook"));
+    }
+
+    #[test]
+    fn prebuilt_without_wrapper_rejects_patched_options() {
+        let config = with(&[("PLUTOW_ILP_RUNTIME", None)]);
+        assert_eq!(
+            config.validate_prebuilt(false),
+            [Diagnostic::Error(
+                "`opt_ilp_runtime` needs Pluto to be built by pluto-build, not a prebuilt Pluto"
+                    .into()
+            )]
+        );
+        assert_eq!(
+            config.validate_prebuilt(true),
+            [Diagnostic::Warning(
+                "options don't affect a prebuilt Pluto, make sure they match how it was built"
+                    .into()
+            )]
+        );
+    }
+}
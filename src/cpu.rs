@@ -0,0 +1,44 @@
+/// The instructions Pluto and Soup may use unconditionally. See [`Build::cpu_baseline`][crate::Build::cpu_baseline].
+///
+/// Whatever the baseline, Soup's accelerated AES, SHA, CRC32 and hardware RNG are compiled separately
+/// and only used if the CPU running the program supports them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CpuBaseline {
+    /// Only what every CPU of the target supports, so one binary works everywhere.
+    /// Faster instructions are picked at runtime.
+    #[default]
+    Portable,
+    /// Everything the CPU of the build machine supports (`-march=native`).
+    /// The binary may crash with an illegal instruction on other CPUs.
+    Native,
+    /// Everything the given `-march` supports, e.g. `x86-64-v3` or `armv8.2-a`.
+    /// The binary may crash with an illegal instruction on older CPUs.
+    March(String),
+}
+
+/// Applies `baseline` to `build`.
+pub(crate) fn apply(build: &mut cc::Build, baseline: &CpuBaseline) {
+    match baseline {
+        CpuBaseline::Portable => {}
+        CpuBaseline::Native => {
+            build.flag_if_supported("-march=native");
+        }
+        CpuBaseline::March(march) => {
+            build.flag_if_supported(format!("-march={march}"));
+        }
+    }
+}
+
+/// The flags enabling the intrinsics used by Soup/Intrin on the architecture of `target`,
+/// or `None` if Soup has no intrinsics for it.
+/// Soup checks that the CPU supports them before calling into Soup/Intrin, so nothing else is compiled with them.
+pub(crate) fn intrin_flags(target: &str) -> Option<&'static [&'static str]> {
+    match target.split('-').next().unwrap_or_default() {
+        "x86_64" => Some(&[
+            "-maes", "-mpclmul", "-mrdrnd", "-mrdseed", "-msha", "-msse4.1",
+        ]),
+        "aarch64" | "arm64e" | "arm64ec" => Some(&["-march=armv8-a+crypto+crc"]),
+        // Soup only uses its intrinsics on 64-bit targets, so not on 32-bit x86 and ARM
+        _ => None,
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// An error that occurred while configuring or compiling Pluto.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An environment variable that cargo sets for build scripts is missing,
+    /// most likely because pluto-build isn't used from a build script.
+    MissingEnv(&'static str),
+    /// Pluto can't be built for the target triple.
+    UnsupportedTarget(String),
+    /// A source file or directory of Pluto is missing or can't be read.
+    MissingSources {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A file generated in `OUT_DIR` can't be written.
+    Output {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The C++ compiler failed. The compiler output has already been printed by cargo.
+    Compiler(cc::Error),
+    /// Linking the shared library failed. See [`Build::shared`][crate::Build::shared].
+    Linker(String),
+    /// A script passed to [`Build::precompile_scripts`][crate::Build::precompile_scripts]
+    /// or [`Build::check_scripts`][crate::Build::check_scripts] has a syntax error.
+    Script { path: PathBuf, message: String },
+    /// The options in `Cargo.toml` or `pluto.toml` can't be read. See [`Build::from_manifest`][crate::Build::from_manifest].
+    Manifest { path: PathBuf, message: String },
+    /// The options contradict each other. See the [`Build`][crate::Build] `opt_*` methods.
+    InvalidOption(String),
+    /// `PLUTO_LIB_DIR` doesn't contain a Pluto library.
+    LibraryNotFound(PathBuf),
+    /// The prebuilt Pluto library can't be read to check its version.
+    Prebuilt {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The prebuilt Pluto library was built by an older pluto-build, and lacks functions `pluto-ffi` needs with the options.
+    OutdatedPrebuilt { path: PathBuf, missing: Vec<String> },
+    /// The prebuilt Pluto library isn't the version pluto-build was made for.
+    VersionMismatch {
+        expected: String,
+        found: Option<String>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingEnv(var) => write!(
+                f,
+                "environment variable `{var}` isn't set, pluto-build must be used from a build script"
+            ),
+            Self::UnsupportedTarget(target) => write!(f, "don't know how to build Pluto for {target}"),
+            Self::MissingSources { path, source } => {
+                write!(f, "can't read Pluto sources at {}: {source}", path.display())
+            }
+            Self::Output { path, source } => write!(f, "can't write {}: {source}", path.display()),
+            Self::Compiler(err) => write!(f, "failed to compile Pluto: {err}"),
+            Self::Linker(output) => write!(f, "failed to link Pluto: {output}"),
+            Self::Script { path, message } => {
+                write!(f, "can't compile script {}: {message}", path.display())
+            }
+            Self::Manifest { path, message } => {
+                write!(f, "invalid Pluto build options in {}: {message}", path.display())
+            }
+            Self::InvalidOption(msg) => write!(f, "invalid Pluto build options: {msg}"),
+            Self::LibraryNotFound(dir) => write!(f, "no Pluto library found in {}", dir.display()),
+            Self::Prebuilt { path, source } => {
+                write!(f, "can't read the prebuilt Pluto at {}: {source}", path.display())
+            }
+            Self::OutdatedPrebuilt { path, missing } => write!(
+                f,
+                "the prebuilt Pluto at {} was built by an older pluto-build and lacks {}, rebuild it with this version",
+                path.display(),
+                missing.join(", ")
+            ),
+            Self::VersionMismatch { expected, found: Some(found) } => {
+                write!(f, "expected prebuilt Pluto {expected}, but found Pluto {found}")
+            }
+            Self::VersionMismatch { expected, found: None } => {
+                write!(f, "expected prebuilt Pluto {expected}, but the library has no version")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::MissingSources { source, .. }
+            | Self::Output { source, .. }
+            | Self::Prebuilt { source, .. } => Some(source),
+            Self::Compiler(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<cc::Error> for Error {
+    fn from(err: cc::Error) -> Self {
+        Self::Compiler(err)
+    }
+}
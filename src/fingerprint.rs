@@ -0,0 +1,175 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+
+use crate::Error;
+
+/// A SHA-256 hash of everything that affects the compiled Pluto: the sources and patches,
+/// the compiler and its arguments (including the defines), and the way it's linked.
+/// It keys the cache shared between crates, so it must not depend on the Rust toolchain building them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fingerprint(String);
+
+impl Fingerprint {
+    pub(crate) fn new(
+        builds: &[&cc::Build],
+        dirs: &[&Path],
+        extra: &[&str],
+    ) -> Result<Self, Error> {
+        let mut hasher = Sha256::new();
+        for build in builds {
+            let compiler = build.try_get_compiler()?;
+            update(&mut hasher, compiler.path().as_os_str().as_encoded_bytes());
+            update(&mut hasher, &(compiler.args().len() as u64).to_le_bytes());
+            for arg in compiler.args() {
+                update(&mut hasher, arg.as_encoded_bytes());
+            }
+            // the same path can be a different compiler after an update
+            let version = Command::new(compiler.path()).arg("--version").output();
+            if let Ok(version) = version {
+                update(&mut hasher, &version.stdout);
+                update(&mut hasher, &version.stderr);
+            }
+        }
+        for dir in dirs {
+            let files = files(dir)?;
+            update(&mut hasher, &(files.len() as u64).to_le_bytes());
+            for file in files {
+                let contents = std::fs::read(&file).map_err(|source| Error::MissingSources {
+                    path: file.clone(),
+                    source,
+                })?;
+                let name = file.strip_prefix(dir).unwrap_or(&file).to_string_lossy();
+                // the same on every platform
+                update(&mut hasher, name.replace('\\', "/").as_bytes());
+                update(&mut hasher, &contents);
+            }
+        }
+        for extra in extra {
+            update(&mut hasher, extra.as_bytes());
+        }
+        let hash = hasher.finalize();
+        Ok(Self(
+            hash.iter().map(|byte| format!("{byte:02x}")).collect(),
+        ))
+    }
+
+    /// Whether the artifacts in `out_dir` were compiled with this fingerprint.
+    pub(crate) fn is_fresh(&self, out_dir: &Path) -> bool {
+        std::fs::read_to_string(Self::file(out_dir)).is_ok_and(|saved| saved == self.0)
+    }
+
+    /// Forgets the fingerprint of the artifacts in `out_dir`, before they're recompiled.
+    pub(crate) fn invalidate(out_dir: &Path) {
+        let _ = std::fs::remove_file(Self::file(out_dir));
+    }
+
+    /// Remembers that the artifacts in `out_dir` were compiled with this fingerprint.
+    pub(crate) fn save(&self, out_dir: &Path) {
+        // at worst, Pluto is recompiled next time
+        let _ = std::fs::write(Self::file(out_dir), &self.0);
+    }
+
+    fn file(out_dir: &Path) -> PathBuf {
+        out_dir.join("pluto.fingerprint")
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Hashes `bytes` prefixed with their length, so the boundaries between inputs are part of the hash.
+fn update(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+/// All files in `dir` and its subdirectories, sorted.
+fn files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|source| Error::MissingSources {
+            path: dir.clone(),
+            source,
+        })?;
+        for entry in entries.filter_map(|e| e.ok()) {
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => dirs.push(entry.path()),
+                _ => files.push(entry.path()),
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pluto's sources and patches, each with a single file.
+    fn dirs(name: &str) -> (PathBuf, PathBuf) {
+        let dir = crate::test_dir(&format!("fingerprint-{name}"));
+        let (src, patches) = (dir.join("src"), dir.join("patches"));
+        for (dir, file) in [(&src, "lvm.cpp"), (&patches, "hooks.cpp")] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join(file), "int x;").unwrap();
+        }
+        (src, patches)
+    }
+
+    fn fingerprint(src: &Path, patches: &Path, define: Option<&str>) -> Fingerprint {
+        let mut build = cc::Build::new();
+        build
+            .cpp(true)
+            .target("x86_64-unknown-linux-gnu")
+            .host("x86_64-unknown-linux-gnu")
+            .opt_level(0)
+            .cargo_metadata(false);
+        if let Some(define) = define {
+            build.define(define, None);
+        }
+        Fingerprint::new(&[&build], &[src, patches], &["static"]).unwrap()
+    }
+
+    #[test]
+    fn stable() {
+        let (src, patches) = dirs("stable");
+        let first = fingerprint(&src, &patches, None);
+        assert_eq!(first, fingerprint(&src, &patches, None));
+        assert_eq!(first.to_string().len(), 64);
+    }
+
+    #[test]
+    fn changes_with_the_inputs() {
+        let (src, patches) = dirs("changes");
+        let original = fingerprint(&src, &patches, None);
+        assert_ne!(original, fingerprint(&src, &patches, Some("PLUTO_VMDUMP")));
+
+        std::fs::write(src.join("lvm.cpp"), "int y;").unwrap();
+        let source_changed = fingerprint(&src, &patches, None);
+        assert_ne!(original, source_changed);
+
+        std::fs::write(patches.join("hooks.cpp"), "int y;").unwrap();
+        assert_ne!(source_changed, fingerprint(&src, &patches, None));
+    }
+
+    #[test]
+    fn freshness() {
+        let (src, patches) = dirs("freshness");
+        let out_dir = src.parent().unwrap().join("out");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let fingerprint = fingerprint(&src, &patches, None);
+        assert!(!fingerprint.is_fresh(&out_dir));
+        fingerprint.save(&out_dir);
+        assert!(fingerprint.is_fresh(&out_dir));
+        Fingerprint::invalidate(&out_dir);
+        assert!(!fingerprint.is_fresh(&out_dir));
+    }
+}
@@ -0,0 +1,59 @@
+/// Content moderation hooks that can be routed to the typed hook registry of `pluto-ffi`
+/// using [`Build::opt_ffi_hook`][crate::Build::opt_ffi_hook].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hook {
+    /// Called for code passed to `load`. See [`Build::opt_load_hook`][crate::Build::opt_load_hook].
+    Load,
+    /// Called for files loaded by `require` and `dofile`. See [`Build::opt_loadfile_hook`][crate::Build::opt_loadfile_hook].
+    LoadFile,
+    /// Called for C libraries loaded by `require` and `package.loadlib`. See [`Build::opt_loadclib_hook`][crate::Build::opt_loadclib_hook].
+    LoadClib,
+    /// Called for reads of a file's contents or metadata. See [`Build::opt_read_file_hook`][crate::Build::opt_read_file_hook].
+    ReadFile,
+    /// Called for writes of a file's contents or metadata. See [`Build::opt_write_file_hook`][crate::Build::opt_write_file_hook].
+    WriteFile,
+    /// Called for HTTP requests. See [`Build::opt_http_request_hook`][crate::Build::opt_http_request_hook].
+    HttpRequest,
+    /// Called for calls to foreign functions. See [`Build::opt_ffi_call_hook`][crate::Build::opt_ffi_call_hook].
+    FfiCall,
+}
+
+impl Hook {
+    /// All hooks supported by Pluto.
+    pub const ALL: &'static [Hook] = &[
+        Hook::Load,
+        Hook::LoadFile,
+        Hook::LoadClib,
+        Hook::ReadFile,
+        Hook::WriteFile,
+        Hook::HttpRequest,
+        Hook::FfiCall,
+    ];
+
+    /// The Pluto define that names the function called for this hook.
+    pub fn define(&self) -> &'static str {
+        match self {
+            Self::Load => "PLUTO_LOAD_HOOK",
+            Self::LoadFile => "PLUTO_LOADFILE_HOOK",
+            Self::LoadClib => "PLUTO_LOADCLIB_HOOK",
+            Self::ReadFile => "PLUTO_READ_FILE_HOOK",
+            Self::WriteFile => "PLUTO_WRITE_FILE_HOOK",
+            Self::HttpRequest => "PLUTO_HTTP_REQUEST_HOOK",
+            Self::FfiCall => "PLUTO_FFI_CALL_HOOK",
+        }
+    }
+
+    /// The function Pluto calls for this hook when it's routed to `pluto-ffi`.
+    /// It looks up the policy of the calling state and forwards to the trampoline exported by `pluto-ffi`.
+    pub fn trampoline(&self) -> &'static str {
+        match self {
+            Self::Load => "plutow_load_hook",
+            Self::LoadFile => "plutow_loadfile_hook",
+            Self::LoadClib => "plutow_loadclib_hook",
+            Self::ReadFile => "plutow_read_file_hook",
+            Self::WriteFile => "plutow_write_file_hook",
+            Self::HttpRequest => "plutow_http_request_hook",
+            Self::FfiCall => "plutow_ffi_call_hook",
+        }
+    }
+}
@@ -1,5 +1,8 @@
 #![doc = include_str!("../README.md")]
 
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::str::FromStr;
 
 const FILES: &[&str] = &[
@@ -54,116 +57,694 @@ const FILES: &[&str] = &[
     "lcanvas",
 ];
 
+mod binaries;
+mod cache;
+mod config;
+mod cpu;
+mod error;
+mod fingerprint;
+mod hooks;
+mod libraries;
+mod manifest;
 mod options;
+mod overrides;
+mod prebuilt;
+mod profile;
+mod sandbox;
+mod scripts;
+mod soup;
+mod unity;
 
-pub struct Build(cc::Build);
+pub use binaries::Binaries;
+pub use cpu::CpuBaseline;
+pub use error::Error;
+pub use hooks::Hook;
+pub use libraries::Library;
+pub use profile::Profile;
+pub use sandbox::Sandbox;
+
+pub struct Build {
+    cc: cc::Build,
+    soup: cc::Build,
+    version: &'static str,
+    src_dir: PathBuf,
+    sources: Vec<PathBuf>,
+    patches: Vec<PathBuf>,
+    target: String,
+    shared: bool,
+    cache_dir: Option<PathBuf>,
+    profile: Option<Profile>,
+    api_check: Option<bool>,
+    cpu_baseline: CpuBaseline,
+    libraries: Vec<Library>,
+    unity: bool,
+    /// The static libraries that have been compiled into `OUT_DIR` by `compile()`, reused by `compile_binaries()`.
+    compiled: Vec<&'static str>,
+    /// The binaries built by `compile_binaries()`, reused by the script methods.
+    binaries: Option<Binaries>,
+    /// The scripts whose warnings have been reported, so checking and compiling a script reports them once.
+    reported_scripts: HashSet<PathBuf>,
+    link_libs: &'static [&'static str],
+    config: config::Config,
+}
 
 impl Build {
+    /// Creates a build for the current target.
+    ///
+    /// Panics if Pluto can't be built for the target. See [`try_new`][Self::try_new].
     pub fn new() -> Self {
+        Self::try_new().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a build for the current target, or returns an error if Pluto can't be built for it.
+    pub fn try_new() -> Result<Self, Error> {
+        let target = std::env::var("TARGET").map_err(|_| Error::MissingEnv("TARGET"))?;
+        Self::for_target(target)
+    }
+
+    /// Creates a build for `target`, without reading the environment of the build script.
+    pub(crate) fn for_target(target: String) -> Result<Self, Error> {
         let mut build = cc::Build::new();
 
-        build.cpp(true).std("c++17").opt_level(3);
+        build.cpp(true).std("c++17");
         build.warnings(false).pic(true);
 
-        build.define("SOUP_USE_INTRIN", None);
         build.define("_CRT_SECURE_NO_WARNINGS", None);
-        build.define("NDEBUG", None);
         build.define("_CONSOLE", None);
         build.flag_if_supported("/Zc:__cplusplus");
         build.flag_if_supported("/wd4646");
 
-        if cfg!(debug_assertions) {
-            build.opt_level(0);
-            build.define("LUA_USE_APICHECK", None);
-        }
-
         // update the package version in Cargo.toml to match the Pluto version!
         let (_, version) = env!("CARGO_PKG_VERSION").split_once('-').unwrap();
-        let root_dir = std::path::PathBuf::from_str(env!("CARGO_MANIFEST_DIR")).unwrap();
+        let root_dir = PathBuf::from_str(env!("CARGO_MANIFEST_DIR")).unwrap();
         let src_dir = root_dir.join(format!("Pluto-{version}/src"));
 
-        build_soup_dependencies(&build, &src_dir);
+        if cpu::intrin_flags(&target).is_some() {
+            build.define("SOUP_USE_INTRIN", None);
+        }
 
-        let target = std::env::var("TARGET").unwrap();
-        match target {
+        let soup = build.clone();
+        let link_libs: &[&str] = match target {
+            _ if target.contains("linux-musl") => {
+                // dlopen is part of musl's libc
+                build.define("LUA_USE_LINUX", None);
+                &[]
+            }
             _ if target.contains("linux") => {
                 build.define("LUA_USE_LINUX", None);
-                println!("cargo:rustc-link-lib=dl");
+                &["dl"]
             }
             _ if target.contains("apple-darwin") => {
                 build
                     .define("LUA_USE_MACOSX", None)
                     .define("LUA_USE_READLINE", None);
-                println!("cargo:rustc-link-lib=resolv");
-                println!("cargo:rustc-link-lib=readline");
+                &["resolv", "readline"]
             }
             _ if target.ends_with("bsd") => {
                 build
                     .define("LUA_USE_LINUX", None)
                     .define("LUA_USE_READLINE", None)
                     .include("/usr/include/edit");
-                println!("cargo:rustc-link-lib=edit");
+                &["edit"]
             }
             _ if target.contains("windows") => {
                 build.define("LUA_USE_WINDOWS", None);
+                &[]
             }
-            _ => panic!("don't know how to build Pluto for {}", target),
+            _ => return Err(Error::UnsupportedTarget(target)),
         };
 
         build.include(&src_dir);
+        let mut sources = Vec::with_capacity(FILES.len());
         for file in FILES {
-            build.file(src_dir.join(file).with_extension("cpp"));
+            let path = src_dir.join(file).with_extension("cpp");
+            std::fs::metadata(&path).map_err(|source| Error::MissingSources {
+                path: path.clone(),
+                source,
+            })?;
+            sources.push(path);
         }
 
-        let patches_dir = root_dir.join("patches");
-        build.add_files_by_ext(&patches_dir, "cpp");
+        let patches = files_by_ext(&root_dir.join("patches"), "cpp")?;
 
-        Self(build)
+        Ok(Self {
+            cc: build,
+            soup,
+            version,
+            src_dir,
+            sources,
+            patches,
+            target,
+            shared: false,
+            cache_dir: None,
+            profile: None,
+            api_check: None,
+            cpu_baseline: CpuBaseline::Portable,
+            libraries: Library::from_features(),
+            unity: false,
+            compiled: Vec::new(),
+            binaries: None,
+            reported_scripts: HashSet::new(),
+            link_libs,
+            config: Default::default(),
+        })
     }
 
+    /// Creates a build for the current target with the options of the crate's manifest.
+    ///
+    /// Panics if Pluto can't be built for the target or the options are invalid. See [`try_from_manifest`][Self::try_from_manifest].
+    pub fn from_manifest() -> Self {
+        Self::try_from_manifest().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a build for the current target with the options of the `[package.metadata.pluto]` table in the crate's
+    /// `Cargo.toml`, or if there is none, of the nearest `pluto.toml` in the crate's directory or its parents.
+    /// Without either, this is the same as [`try_new`][Self::try_new].
+    ///
+    /// The keys are the names of the `opt_*` methods without the prefix, e.g. `ilp_max_iterations = 10000`.
+    /// Options without arguments are set with `true`, `ffi_hook` takes a list of hooks like `["load", "read_file"]`,
+    /// and `sandbox` one of `"strict"`, `"network_only"` or `"trusted"`. Unknown keys are returned as [`Error::Manifest`].
+    /// Further options can still be set on the returned build.
+    pub fn try_from_manifest() -> Result<Self, Error> {
+        let mut build = Self::try_new()?;
+        if let Some(manifest) = manifest::Manifest::find()? {
+            manifest.apply(&mut build)?;
+        }
+        Ok(build)
+    }
+
+    /// If `true`, Pluto is built as a shared library (`libpluto.so`, `libpluto.dylib` or `pluto.dll`) and linked dynamically.
+    /// Only the functions marked with `PLUTO_DLLSPEC` (Lua's and Pluto's API) are exported.
+    ///
+    /// This lets several Rust cdylib plugins loaded into one host share a single Pluto runtime,
+    /// and native C modules loaded via `require` resolve `lua_*` symbols. The library is placed in `OUT_DIR`,
+    /// so it must be shipped next to your binary or be on the library search path.
+    ///
+    /// NOTE: Hooks can't be routed to `pluto-ffi` (see [`opt_ffi_hook`][Self::opt_ffi_hook]) in this mode,
+    /// since the shared library can't call back into it.
+    pub fn shared(&mut self, shared: bool) -> &mut Self {
+        self.shared = shared;
+        self
+    }
+
+    /// Caches the compiled Pluto in `dir`, so other crates building the same Pluto reuse it instead of compiling it again.
+    /// Entries are keyed by everything that affects the build: the Pluto version, the target, the compiler and its flags,
+    /// and the options. Concurrent build scripts wait for each other using a lock file per entry.
+    ///
+    /// Defaults to the `PLUTO_CACHE_DIR` environment variable. A relative path is relative to the crate being built,
+    /// so for a workspace, set it in `.cargo/config.toml`:
+    ///
+    /// ```toml
+    /// [env]
+    /// PLUTO_CACHE_DIR = { value = "target/pluto-cache", relative = true }
+    /// ```
+    pub fn cache_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Overrides the optimization level and assertions Pluto is compiled with.
+    /// By default, they follow the Cargo profile of the crate being built. See [`Profile`].
+    pub fn profile(&mut self, profile: Profile) -> &mut Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// If `true`, Pluto checks the arguments of C API calls (`LUA_USE_APICHECK`), which catches misuse of the API
+    /// at the cost of speed. Defaults to whether the profile has assertions.
+    pub fn api_check(&mut self, api_check: bool) -> &mut Self {
+        self.api_check = Some(api_check);
+        self
+    }
+
+    /// Sets the instructions Pluto may use unconditionally. Defaults to [`CpuBaseline::Portable`],
+    /// which works on every CPU of the target and picks faster instructions at runtime.
+    pub fn cpu_baseline(&mut self, baseline: CpuBaseline) -> &mut Self {
+        self.cpu_baseline = baseline;
+        self
+    }
+
+    /// Only builds the given Pluto libraries, leaving the others out of the binary and `package.preload`.
+    /// Defaults to the libraries enabled by the `lib-*` features, which are all enabled by default.
+    ///
+    /// Enable the same `lib-*` features for `pluto-ffi`, so `PlutoLibrary` only has the variants that exist.
+    pub fn libraries(&mut self, libraries: &[Library]) -> &mut Self {
+        self.libraries = libraries.to_vec();
+        self
+    }
+
+    /// If `true`, Pluto's and Soup's sources are amalgamated into a few translation units in `OUT_DIR`, which
+    /// compiles Pluto several times faster from scratch. Sources that can't share a translation unit, e.g. because
+    /// they declare the same `static` functions, are kept apart. Defaults to `false`.
+    pub fn unity(&mut self, unity: bool) -> &mut Self {
+        self.unity = unity;
+        self
+    }
+
+    /// Compiles and links Pluto with the configured options.
+    ///
+    /// Panics if the options contradict each other or compilation fails. See [`try_compile`][Self::try_compile].
     pub fn compile(&mut self) {
-        self.0.compile("plutostatic");
+        self.try_compile().unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Compiles and links Pluto with the configured options.
+    ///
+    /// Combinations of options that have no effect are reported as cargo warnings,
+    /// and contradicting options are returned as [`Error::InvalidOption`] before anything is compiled.
+    pub fn try_compile(&mut self) -> Result<(), Error> {
+        self.apply_env_overrides()?;
+        report(self.config.validate())?;
+        if self.crt_static() {
+            self.link_static_cpp_stdlib()?;
+        }
+
+        if let Some(prebuilt) = prebuilt::Prebuilt::probe(self.version)? {
+            let patched = prebuilt.has_wrapper(&self.config.wrapper_functions())?;
+            report(self.config.validate_prebuilt(patched))?;
+            if self.libraries.len() != Library::ALL.len() {
+                println!("cargo:warning=pluto-build: `libraries` doesn't affect a prebuilt Pluto");
+            }
+            if !patched {
+                // the functions used by pluto-ffi only need Pluto's public API
+                let mut build = self.configured(&self.cc);
+                for (name, value) in self.config.defines() {
+                    build.define(name, value);
+                }
+                build.files(&self.patches).try_compile("plutowrapper")?;
+            }
+            for lib in self.link_libs {
+                println!("cargo:rustc-link-lib={lib}");
+            }
+            return Ok(());
+        }
+
+        if self.shared && self.config.is_defined("PLUTOW_FFI_HOOKS") {
+            return Err(Error::InvalidOption(
+                "`opt_ffi_hook` can't be used with `shared(true)`, the shared library can't call into pluto-ffi".into(),
+            ));
+        }
+
+        if self.shared && self.crt_static() {
+            return Err(Error::InvalidOption(
+                "`shared(true)` can't be used for a statically linked musl target, build with `-C target-feature=-crt-static`".into(),
+            ));
+        }
+
+        let patches_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("patches");
+        println!("cargo:rerun-if-changed={}", self.src_dir.display());
+        println!("cargo:rerun-if-changed={}", patches_dir.display());
+
+        let mut soup = self.configured(&self.soup);
+        if self.shared {
+            // Soup is linked into the shared library, and only Pluto's API is exported
+            soup.cargo_metadata(false)
+                .flags_if_supported(HIDDEN_VISIBILITY);
+        }
+        let mut build = self.configured(&self.cc);
+        for (name, value) in self.config.defines() {
+            build.define(name, value);
+        }
+        self.add_sources(&mut build)?;
+        if self.shared {
+            build.flags_if_supported(HIDDEN_VISIBILITY);
+            if self.target.contains("windows") {
+                build.define("LUA_BUILD_AS_DLL", None);
+            }
+        }
+
+        let out_dir = out_dir()?;
+        let kind = if self.shared { "shared" } else { "static" };
+        let mode = if self.unity { "unity" } else { "files" };
+        let fingerprint = fingerprint::Fingerprint::new(
+            &[&soup, &build],
+            &[&self.src_dir, &patches_dir],
+            &[self.version, &self.target, kind, mode],
+        )?;
+        // before the freshness check, so a fresh build still reruns when the cache directory changes
+        println!("cargo:rerun-if-env-changed=PLUTO_CACHE_DIR");
+        if fingerprint.is_fresh(&out_dir) {
+            self.link_compiled(&out_dir);
+            return Ok(());
+        }
+
+        let cache_dir = (self.cache_dir.clone())
+            .or_else(|| std::env::var_os("PLUTO_CACHE_DIR").map(PathBuf::from));
+        let entry = cache_dir.and_then(|dir| cache::Cache::new(dir).lock(&fingerprint));
+        let artifacts = self.artifacts();
+        if entry
+            .as_ref()
+            .is_some_and(|entry| entry.restore(&artifacts, &out_dir))
+        {
+            fingerprint.save(&out_dir);
+            self.link_compiled(&out_dir);
+            return Ok(());
+        }
+
+        fingerprint::Fingerprint::invalidate(&out_dir);
+        let soup_sources = self.soup_sources(&build)?;
+        build_soup_dependencies(
+            &soup,
+            &self.src_dir,
+            &soup_sources,
+            &self.target,
+            !self.shared,
+        )?;
+        self.compiled.extend(soup_libs(&self.target));
+        for lib in self.link_libs {
+            println!("cargo:rustc-link-lib={lib}");
+        }
+        if self.shared {
+            self.link_shared(&build)?;
+        } else {
+            build.try_compile("plutostatic")?;
+            self.compiled.push("plutostatic");
+        }
+        fingerprint.save(&out_dir);
+        if let Some(entry) = entry {
+            entry.store(&artifacts, &out_dir);
+        }
+        Ok(())
+    }
+
+    /// Adds Pluto's sources and patches to `build`, leaving out the libraries that aren't wanted.
+    fn add_sources(&self, build: &mut cc::Build) -> Result<(), Error> {
+        for lib in Library::ALL
+            .iter()
+            .filter(|lib| !self.libraries.contains(lib))
+        {
+            build.define(lib.exclude_define(), None);
+        }
+        if self.unity {
+            let units =
+                unity::amalgamate(&self.pluto_sources(), &out_dir()?.join("unity"), "pluto")?;
+            build.files(units);
+        } else {
+            build.files(self.pluto_sources());
+        }
+        build.files(&self.patches);
+        Ok(())
+    }
+
+    /// Pluto's sources, without the libraries that aren't wanted.
+    fn pluto_sources(&self) -> Vec<&PathBuf> {
+        let excluded = (Library::ALL.iter())
+            .filter(|lib| !self.libraries.contains(lib))
+            .map(|lib| lib.file())
+            .collect::<Vec<_>>();
+        (self.sources.iter())
+            .filter(|path| {
+                let stem = path.file_stem().and_then(|stem| stem.to_str());
+                !stem.is_some_and(|stem| excluded.contains(&stem))
+            })
+            .collect()
+    }
+
+    /// The Soup sources needed by the sources compiled by `build`, see [`add_sources`][Self::add_sources].
+    /// In unity mode, these are amalgamated too.
+    fn soup_sources(&self, build: &cc::Build) -> Result<Vec<PathBuf>, Error> {
+        let soup_dir = self.src_dir.join("vendor/Soup/soup");
+        let roots = (self.pluto_sources().into_iter())
+            .chain(&self.patches)
+            .cloned()
+            .collect();
+        let sources = match soup::needed_sources(build, &soup_dir, roots)? {
+            Some(sources) => sources,
+            None => files_by_ext(&soup_dir, "cpp")?,
+        };
+        if self.unity {
+            return unity::amalgamate(&sources, &out_dir()?.join("unity"), "soup");
+        }
+        Ok(sources)
+    }
+
+    /// Whether everything is linked statically, like for musl targets by default.
+    fn crt_static(&self) -> bool {
+        let features = std::env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default();
+        self.target.contains("musl") && features.split(',').any(|feature| feature == "crt-static")
+    }
+
+    /// Lets the linker find the static C++ standard library of the compiler, e.g. of a musl cross compiler
+    /// while Rust links with the host's `cc`.
+    fn link_static_cpp_stdlib(&self) -> Result<(), Error> {
+        let compiler = self.cc.try_get_compiler()?;
+        let output = (compiler.to_command())
+            .arg("-print-file-name=libstdc++.a")
+            .output();
+        if let Ok(output) = output {
+            let file = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+            // only the file name is printed if the compiler doesn't have it
+            if let Some(dir) = file.parent().filter(|dir| dir.is_absolute()) {
+                println!("cargo:rustc-link-search=native={}", dir.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// A copy of `build` with the profile and CPU baseline applied.
+    fn configured(&self, build: &cc::Build) -> cc::Build {
+        let mut build = build.clone();
+        profile::apply(&mut build, self.profile, self.api_check);
+        cpu::apply(&mut build, &self.cpu_baseline);
+        build
+    }
+
+    /// The files `compile()` puts into `OUT_DIR`.
+    fn artifacts(&self) -> Vec<String> {
+        let msvc = self.target.contains("msvc");
+        let mut libs = soup_libs(&self.target).to_vec();
+        let mut files = Vec::new();
+        if self.shared {
+            files.push(self.shared_lib_name().to_owned());
+            if msvc {
+                libs.push("pluto");
+            } else if self.target.contains("windows") {
+                files.push("libpluto.dll.a".to_owned());
+            }
+        } else {
+            libs.push("plutostatic");
+        }
+        files.extend(libs.into_iter().map(|lib| match msvc {
+            true => format!("{lib}.lib"),
+            false => format!("lib{lib}.a"),
+        }));
+        files
+    }
+
+    fn shared_lib_name(&self) -> &'static str {
+        if self.target.contains("apple") {
+            "libpluto.dylib"
+        } else if self.target.contains("windows") {
+            "pluto.dll"
+        } else {
+            "libpluto.so"
+        }
+    }
+
+    /// Links Pluto compiled by an earlier run of the build script, emitting what compiling it would have.
+    fn link_compiled(&mut self, out_dir: &Path) {
+        println!("cargo:rustc-link-search=native={}", out_dir.display());
+        if self.shared {
+            println!("cargo:rustc-link-lib=dylib=pluto");
+        } else {
+            for lib in soup_libs(&self.target) {
+                println!("cargo:rustc-link-lib=static={lib}");
+            }
+            for lib in self.link_libs {
+                println!("cargo:rustc-link-lib={lib}");
+            }
+            println!("cargo:rustc-link-lib=static=plutostatic");
+            if let Some(stdlib) = cpp_stdlib() {
+                println!("cargo:rustc-link-lib={stdlib}");
+            }
+            self.compiled.push("plutostatic");
+        }
+        self.compiled.extend(soup_libs(&self.target));
+    }
+
+    fn link_shared(&self, build: &cc::Build) -> Result<(), Error> {
+        let out_dir = out_dir()?;
+        let objects = build.try_compile_intermediates()?;
+        let compiler = build.try_get_compiler()?;
+        let mut cmd = compiler.to_command();
+        cmd.args(&objects);
+
+        let soup_libs = soup_libs(&self.target);
+        if compiler.is_like_msvc() {
+            cmd.args(
+                soup_libs
+                    .iter()
+                    .map(|lib| out_dir.join(lib).with_extension("lib")),
+            )
+            .arg("/LD")
+            .arg(format!("/Fe{}", out_dir.join("pluto.dll").display()));
+        } else {
+            let apple = self.target.contains("apple");
+            let windows = self.target.contains("windows");
+            let file = self.shared_lib_name();
+            cmd.args(
+                soup_libs
+                    .iter()
+                    .map(|lib| out_dir.join(format!("lib{lib}.a"))),
+            )
+            .arg(if apple { "-dynamiclib" } else { "-shared" })
+            .arg("-o")
+            .arg(out_dir.join(file));
+            if apple {
+                cmd.arg(format!("-Wl,-install_name,@rpath/{file}"));
+            } else if windows {
+                let implib = out_dir.join("libpluto.dll.a");
+                cmd.arg(format!("-Wl,--out-implib,{}", implib.display()));
+            } else {
+                cmd.arg(format!("-Wl,-soname,{file}"));
+            }
+            for lib in self.link_libs {
+                cmd.arg(format!("-l{lib}"));
+            }
+            if let Some(stdlib) = cpp_stdlib() {
+                cmd.arg(format!("-l{stdlib}"));
+            }
+        }
+
+        run_linker(&mut cmd)?;
+        println!("cargo:rustc-link-search=native={}", out_dir.display());
+        println!("cargo:rustc-link-lib=dylib=pluto");
+        Ok(())
     }
 }
 
-fn build_soup_dependencies(b: &cc::Build, src_dir: &std::path::Path) {
-    let soup_intrin_src = src_dir.join("vendor/Soup/Intrin");
-    b.clone()
-        .include(&soup_intrin_src)
-        .add_files_by_ext(&soup_intrin_src, "cpp")
-        .flags(&[
-            "-maes", "-mavx", "-mavx2", "-mpclmul", "-mrdrnd", "-mrdseed", "-msha", "-msse4.1",
-        ])
-        .compile("soupintrin");
-
-    let soup_src = src_dir.join("vendor/Soup/soup");
+fn out_dir() -> Result<PathBuf, Error> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(Error::MissingEnv("OUT_DIR"))?;
+    Ok(PathBuf::from(out_dir))
+}
+
+/// An empty directory for a unit test, named after it.
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("pluto-build-{}", std::process::id()))
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_linker(cmd: &mut Command) -> Result<(), Error> {
+    let output = cmd
+        .output()
+        .map_err(|err| Error::Linker(format!("failed to run {cmd:?}: {err}")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        return Err(Error::Linker(format!("{stdout}{stderr}")));
+    }
+    Ok(())
+}
+
+/// The C++ standard library Pluto needs, which `cc` links automatically when it compiles a static library.
+pub(crate) fn cpp_stdlib() -> Option<String> {
+    println!("cargo:rerun-if-env-changed=CXXSTDLIB");
+    if let Ok(stdlib) = std::env::var("CXXSTDLIB") {
+        return (!stdlib.is_empty()).then_some(stdlib);
+    }
+    let target = std::env::var("TARGET").unwrap_or_default();
+    if target.contains("msvc") {
+        None
+    } else if target.contains("apple") || target.contains("freebsd") || target.contains("openbsd") {
+        Some("c++".into())
+    } else if target.contains("android") {
+        Some("c++_shared".into())
+    } else {
+        Some("stdc++".into())
+    }
+}
+
+/// Prints warnings as cargo warnings and returns errors as [`Error::InvalidOption`].
+fn report(diagnostics: Vec<config::Diagnostic>) -> Result<(), Error> {
+    let mut errors = Vec::new();
+    for diagnostic in diagnostics {
+        match diagnostic {
+            config::Diagnostic::Warning(msg) => println!("cargo:warning=pluto-build: {msg}"),
+            config::Diagnostic::Error(msg) => errors.push(msg),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::InvalidOption(errors.join(", ")));
+    }
+    Ok(())
+}
+
+impl Default for Build {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const SOUP_LIBS: [&str; 2] = ["soup", "soupintrin"];
+
+/// The Soup libraries compiled for `target`, Soup/Intrin is only compiled if it has intrinsics for the target.
+fn soup_libs(target: &str) -> &'static [&'static str] {
+    match cpu::intrin_flags(target) {
+        Some(_) => &SOUP_LIBS,
+        None => &SOUP_LIBS[..1],
+    }
+}
+
+/// Hides everything but `PLUTO_DLLSPEC` functions when building a shared library.
+const HIDDEN_VISIBILITY: &[&str] = &["-fvisibility=hidden", "-fvisibility-inlines-hidden"];
+
+fn build_soup_dependencies(
+    b: &cc::Build,
+    src_dir: &Path,
+    soup_sources: &[PathBuf],
+    target: &str,
+    link: bool,
+) -> Result<(), Error> {
+    if let Some(flags) = cpu::intrin_flags(target) {
+        let soup_intrin_src = src_dir.join("vendor/Soup/Intrin");
+        b.clone()
+            .include(&soup_intrin_src)
+            .add_files_by_ext(&soup_intrin_src, "cpp")?
+            .flags_if_supported(flags)
+            .try_compile("soupintrin")?;
+    }
+
     b.clone()
-        .include(&soup_src)
-        .add_files_by_ext(&soup_src, "cpp")
-        .compile("soup");
+        .include(src_dir.join("vendor/Soup/soup"))
+        .files(soup_sources)
+        .try_compile("soup")?;
+
+    if link {
+        for lib in soup_libs(target) {
+            println!("cargo:rustc-link-lib=static={lib}");
+        }
+    }
+    Ok(())
+}
 
-    println!("cargo:rustc-link-lib=static=soup");
-    println!("cargo:rustc-link-lib=static=soupintrin");
+fn files_by_ext(dir: &Path, ext: &str) -> Result<Vec<PathBuf>, Error> {
+    let entries = std::fs::read_dir(dir).map_err(|source| Error::MissingSources {
+        path: dir.to_owned(),
+        source,
+    })?;
+    Ok(entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.extension() == Some(ext.as_ref()))
+        .collect())
 }
 
 trait BuildExt {
-    fn add_files_by_ext(&mut self, dir: &std::path::Path, ext: &str) -> &mut Self;
-    fn flags(&mut self, flags: &[&str]) -> &mut Self;
+    fn add_files_by_ext(&mut self, dir: &Path, ext: &str) -> Result<&mut Self, Error>;
+    fn flags_if_supported(&mut self, flags: &[&str]) -> &mut Self;
 }
 
 impl BuildExt for cc::Build {
-    fn add_files_by_ext(&mut self, dir: &std::path::Path, ext: &str) -> &mut Self {
-        for entry in std::fs::read_dir(dir)
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension() == Some(ext.as_ref()))
-        {
-            self.file(entry.path());
-        }
-        self
+    fn add_files_by_ext(&mut self, dir: &Path, ext: &str) -> Result<&mut Self, Error> {
+        Ok(self.files(files_by_ext(dir, ext)?))
     }
 
-    fn flags(&mut self, flags: &[&str]) -> &mut Self {
+    fn flags_if_supported(&mut self, flags: &[&str]) -> &mut Self {
         for flag in flags {
             self.flag_if_supported(flag);
         }
@@ -0,0 +1,116 @@
+/// Pluto's own libraries, which can be left out of the build with [`Build::libraries`][crate::Build::libraries].
+/// Lua's standard libraries are always included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Library {
+    Crypto,
+    Json,
+    Base32,
+    Base64,
+    Assert,
+    Vector3,
+    Url,
+    Star,
+    Cat,
+    Http,
+    Scheduler,
+    Socket,
+    BigInt,
+    Xml,
+    Regex,
+    FFI,
+    Canvas,
+}
+
+impl Library {
+    pub const ALL: [Library; 17] = [
+        Self::Crypto,
+        Self::Json,
+        Self::Base32,
+        Self::Base64,
+        Self::Assert,
+        Self::Vector3,
+        Self::Url,
+        Self::Star,
+        Self::Cat,
+        Self::Http,
+        Self::Scheduler,
+        Self::Socket,
+        Self::BigInt,
+        Self::Xml,
+        Self::Regex,
+        Self::FFI,
+        Self::Canvas,
+    ];
+
+    /// The libraries enabled by the `lib-*` features of pluto-build.
+    pub(crate) fn from_features() -> Vec<Library> {
+        let features = [
+            cfg!(feature = "lib-crypto"),
+            cfg!(feature = "lib-json"),
+            cfg!(feature = "lib-base32"),
+            cfg!(feature = "lib-base64"),
+            cfg!(feature = "lib-assert"),
+            cfg!(feature = "lib-vector3"),
+            cfg!(feature = "lib-url"),
+            cfg!(feature = "lib-star"),
+            cfg!(feature = "lib-cat"),
+            cfg!(feature = "lib-http"),
+            cfg!(feature = "lib-scheduler"),
+            cfg!(feature = "lib-socket"),
+            cfg!(feature = "lib-bigint"),
+            cfg!(feature = "lib-xml"),
+            cfg!(feature = "lib-regex"),
+            cfg!(feature = "lib-ffi"),
+            cfg!(feature = "lib-canvas"),
+        ];
+        (Self::ALL.into_iter().zip(features))
+            .filter_map(|(lib, enabled)| enabled.then_some(lib))
+            .collect()
+    }
+
+    /// The source file of the library in Pluto's `src` directory.
+    pub(crate) fn file(self) -> &'static str {
+        match self {
+            Self::Crypto => "lcryptolib",
+            Self::Json => "ljson",
+            Self::Base32 => "lbase32",
+            Self::Base64 => "lbase64",
+            Self::Assert => "lassertlib",
+            Self::Vector3 => "lvector3lib",
+            Self::Url => "lurllib",
+            Self::Star => "lstarlib",
+            Self::Cat => "lcatlib",
+            Self::Http => "lhttplib",
+            Self::Scheduler => "lschedulerlib",
+            Self::Socket => "lsocketlib",
+            Self::BigInt => "lbigint",
+            Self::Xml => "lxml",
+            Self::Regex => "lregex",
+            Self::FFI => "lffi",
+            Self::Canvas => "lcanvas",
+        }
+    }
+
+    /// The define leaving the library out of the preloaded libraries.
+    pub(crate) fn exclude_define(self) -> &'static str {
+        match self {
+            Self::Crypto => "PLUTOW_NO_CRYPTOLIB",
+            Self::Json => "PLUTOW_NO_JSONLIB",
+            Self::Base32 => "PLUTOW_NO_BASE32LIB",
+            Self::Base64 => "PLUTOW_NO_BASE64LIB",
+            Self::Assert => "PLUTOW_NO_ASSERTLIB",
+            Self::Vector3 => "PLUTOW_NO_VECTOR3LIB",
+            Self::Url => "PLUTOW_NO_URLLIB",
+            Self::Star => "PLUTOW_NO_STARLIB",
+            Self::Cat => "PLUTOW_NO_CATLIB",
+            Self::Http => "PLUTOW_NO_HTTPLIB",
+            Self::Scheduler => "PLUTOW_NO_SCHEDULERLIB",
+            Self::Socket => "PLUTOW_NO_SOCKETLIB",
+            Self::BigInt => "PLUTOW_NO_BIGINTLIB",
+            Self::Xml => "PLUTOW_NO_XMLLIB",
+            Self::Regex => "PLUTOW_NO_REGEXLIB",
+            Self::FFI => "PLUTOW_NO_FFILIB",
+            Self::Canvas => "PLUTOW_NO_CANVASLIB",
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::{Build, Error, Hook, Sandbox};
+
+/// The options of a build, read from `[package.metadata.pluto]` or `pluto.toml`.
+/// Keys are the `opt_*` methods of [`Build`] without the prefix.
+pub(crate) struct Manifest {
+    path: PathBuf,
+    options: Table,
+}
+
+macro_rules! options {
+    (
+        flags { $($flag:ident),* $(,)? }
+        strings { $($string:ident),* $(,)? }
+        numbers { $($number:ident),* $(,)? }
+    ) => {
+        /// Calls the `opt_*` method of `build` named by `key` with `value`.
+        /// Returns `Ok(false)` if there's no such option.
+        fn apply_option(build: &mut Build, key: &str, value: &Value) -> Result<bool, String> {
+            match format!("opt_{key}").as_str() {
+                $(stringify!($flag) => {
+                    if expect(value.as_bool(), "a boolean")? {
+                        build.$flag();
+                    }
+                })*
+                $(stringify!($string) => {
+                    build.$string(expect(value.as_str(), "a string")?);
+                })*
+                $(stringify!($number) => {
+                    let number = value.as_integer().and_then(|n| u64::try_from(n).ok()).filter(|n| *n > 0);
+                    build.$number(expect(number, "a positive integer")?);
+                })*
+                "opt_ffi_hook" => {
+                    let hooks = expect(value.as_array(), "an array of hooks")?;
+                    for hook in hooks {
+                        build.opt_ffi_hook(hook_by_name(expect(hook.as_str(), "a hook name")?)?);
+                    }
+                }
+                "opt_sandbox" => {
+                    build.opt_sandbox(sandbox_by_name(expect(value.as_str(), "a sandbox profile")?)?);
+                }
+                _ => return Ok(false),
+            }
+            Ok(true)
+        }
+    };
+}
+
+options! {
+    flags {
+        opt_use_colored_output,
+        opt_short_errors,
+        opt_no_utf8,
+        opt_force_jumptable,
+        opt_no_default_table_metatable,
+        opt_warn_global_shadow,
+        opt_warn_non_portable_code,
+        opt_warn_non_portable_bytecode,
+        opt_warn_non_portable_name,
+        opt_compatible_mode,
+        opt_paranoid_keyword_detection,
+        opt_use_let,
+        opt_use_const,
+        opt_use_global,
+        opt_ilp_enabled,
+        opt_ilp_silent_break,
+        opt_ilp_runtime,
+        opt_etl_enabled,
+        opt_etl_runtime,
+        opt_memory_runtime,
+        opt_vmdump_enabled,
+        opt_vmdump_whitelist,
+        opt_disable_compiled,
+        opt_disable_unmoderated_load,
+        opt_no_filesystem,
+        opt_no_os_execute,
+        opt_no_binaries,
+        opt_no_debuglib,
+        opt_no_corolib,
+        opt_no_socketlib,
+        opt_disable_http_completely,
+        opt_ffi_hooks,
+        opt_disable_length_cache,
+        opt_disable_table_freezing,
+    }
+    strings {
+        opt_common_global_names,
+        opt_ilp_hook_function,
+        opt_ilp_error,
+        opt_etl_timesup,
+        opt_vmdump_ignore,
+        opt_vmdump_allow,
+        opt_vmdump_cond,
+        opt_load_hook,
+        opt_loadfile_hook,
+        opt_loadclib_hook,
+        opt_no_binaries_fail,
+        opt_http_request_hook,
+        opt_read_file_hook,
+        opt_write_file_hook,
+        opt_ffi_call_hook,
+    }
+    numbers {
+        opt_ilp_max_iterations,
+        opt_etl_nanos,
+        opt_memory_limit,
+    }
+}
+
+impl Manifest {
+    /// Reads the `[package.metadata.pluto]` table of the crate's `Cargo.toml`. If there is none, the nearest
+    /// `pluto.toml` in the crate's directory or its parents is read, so a workspace can share one.
+    /// Returns `None` if neither exists.
+    pub(crate) fn find() -> Result<Option<Manifest>, Error> {
+        let dir = PathBuf::from(
+            std::env::var_os("CARGO_MANIFEST_DIR")
+                .ok_or(Error::MissingEnv("CARGO_MANIFEST_DIR"))?,
+        );
+        Self::find_in(&dir)
+    }
+
+    /// Like [`find`][Self::find], for the crate in `dir`.
+    fn find_in(dir: &Path) -> Result<Option<Manifest>, Error> {
+        let cargo_toml = dir.join("Cargo.toml");
+        println!("cargo:rerun-if-changed={}", cargo_toml.display());
+        let metadata = parse(&cargo_toml)?
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("pluto"))
+            .cloned();
+        if let Some(options) = metadata {
+            let options = match options {
+                Value::Table(options) => options,
+                _ => {
+                    return Err(invalid(
+                        &cargo_toml,
+                        "`package.metadata.pluto` must be a table",
+                    ))
+                }
+            };
+            return Ok(Some(Manifest {
+                path: cargo_toml,
+                options,
+            }));
+        }
+
+        let Some(path) = dir
+            .ancestors()
+            .map(|dir| dir.join("pluto.toml"))
+            .find(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+        println!("cargo:rerun-if-changed={}", path.display());
+        let options = parse(&path)?;
+        Ok(Some(Manifest { path, options }))
+    }
+
+    /// Applies the options to `build`.
+    pub(crate) fn apply(&self, build: &mut Build) -> Result<(), Error> {
+        for (key, value) in &self.options {
+            match apply_option(build, key, value) {
+                Ok(true) => {}
+                Ok(false) => return Err(invalid(&self.path, &format!("unknown option `{key}`"))),
+                Err(expected) => {
+                    return Err(invalid(&self.path, &format!("`{key}` must be {expected}")))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse(path: &Path) -> Result<Table, Error> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| invalid(path, &format!("can't read the file: {err}")))?;
+    text.parse()
+        .map_err(|err: toml::de::Error| invalid(path, err.message()))
+}
+
+fn invalid(path: &Path, message: &str) -> Error {
+    Error::Manifest {
+        path: path.to_owned(),
+        message: message.to_owned(),
+    }
+}
+
+fn expect<T>(value: Option<T>, expected: &'static str) -> Result<T, String> {
+    value.ok_or_else(|| expected.to_owned())
+}
+
+fn hook_by_name(name: &str) -> Result<Hook, String> {
+    Ok(match name {
+        "load" => Hook::Load,
+        "loadfile" => Hook::LoadFile,
+        "loadclib" => Hook::LoadClib,
+        "read_file" => Hook::ReadFile,
+        "write_file" => Hook::WriteFile,
+        "http_request" => Hook::HttpRequest,
+        "ffi_call" => Hook::FfiCall,
+        _ => return Err(format!("a hook, not `{name}`")),
+    })
+}
+
+fn sandbox_by_name(name: &str) -> Result<Sandbox, String> {
+    Ok(match name {
+        "strict" => Sandbox::Strict,
+        "network_only" => Sandbox::NetworkOnly,
+        "trusted" => Sandbox::Trusted,
+        _ => {
+            return Err(format!(
+                "`strict`, `network_only` or `trusted`, not `{name}`"
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Diagnostic;
+
+    /// A crate in `workspace/package`, with `Cargo.toml` as given and optionally a `pluto.toml` in `workspace`.
+    fn package(name: &str, cargo_toml: &str, pluto_toml: Option<&str>) -> PathBuf {
+        let workspace = crate::test_dir(&format!("manifest-{name}"));
+        let package = workspace.join("package");
+        std::fs::create_dir_all(&package).unwrap();
+        let cargo_toml = format!("[package]\nname = \"package\"\n{cargo_toml}");
+        std::fs::write(package.join("Cargo.toml"), cargo_toml).unwrap();
+        if let Some(pluto_toml) = pluto_toml {
+            std::fs::write(workspace.join("pluto.toml"), pluto_toml).unwrap();
+        }
+        package
+    }
+
+    fn build(dir: &Path) -> Result<Build, Error> {
+        let mut build = Build::for_target("x86_64-unknown-linux-gnu".into())?;
+        if let Some(manifest) = Manifest::find_in(dir)? {
+            manifest.apply(&mut build)?;
+        }
+        Ok(build)
+    }
+
+    #[test]
+    fn without_options() {
+        let dir = package("none", "", None);
+        assert!(Manifest::find_in(&dir).unwrap().is_none());
+    }
+
+    #[test]
+    fn shared_options() {
+        let contradicting = "ilp_enabled = true\nilp_error = \"error('too many iterations')\"\nilp_silent_break = true\n";
+        let dir = package("shared", "", Some(contradicting));
+        // contradicting options are only reported by `compile()`
+        assert!(matches!(
+            build(&dir).unwrap().config.validate().as_slice(),
+            [Diagnostic::Error(_)]
+        ));
+    }
+
+    #[test]
+    fn package_options_take_precedence() {
+        let metadata = "[package.metadata.pluto]\nilp_enabled = true\n";
+        let dir = package("precedence", metadata, Some("not_an_option = true\n"));
+        assert!(build(&dir).unwrap().config.is_defined("PLUTO_ILP_ENABLE"));
+    }
+
+    #[test]
+    fn options_are_applied() {
+        let metadata = "[package.metadata.pluto]\nsandbox = \"strict\"\nilp_enabled = true\nilp_max_iterations = 1000\nffi_hook = [\"load\", \"loadfile\"]\n";
+        let dir = package("applied", metadata, None);
+        let config = build(&dir).unwrap().config;
+        let defines = config.defines().collect::<Vec<_>>();
+        // the strict sandbox
+        assert!(config.is_defined("PLUTO_NO_FILESYSTEM"));
+        assert!(config.is_defined("PLUTO_DISABLE_HTTP_COMPLETELY"));
+        assert!(defines.contains(&("PLUTO_ILP_ENABLE", None)));
+        assert!(defines.contains(&("PLUTO_ILP_MAX_ITERATIONS", Some("1000"))));
+        assert!(config.is_defined("PLUTOW_FFI_HOOKS"));
+        for hook in [Hook::Load, Hook::LoadFile] {
+            assert!(defines.contains(&(hook.define(), Some(hook.trampoline()))));
+        }
+        assert!(!config.is_defined(Hook::HttpRequest.define()));
+    }
+
+    #[test]
+    fn invalid_options() {
+        for (options, message) in [
+            ("ilp_enabeld = true", "unknown option `ilp_enabeld`"),
+            (
+                "ilp_max_iterations = -1",
+                "`ilp_max_iterations` must be a positive integer",
+            ),
+            ("etl_nanos = 0", "`etl_nanos` must be a positive integer"),
+            (
+                "ffi_hook = [\"load_file\"]",
+                "`ffi_hook` must be a hook, not `load_file`",
+            ),
+            (
+                "ffi_hook = [\"lod\"]",
+                "`ffi_hook` must be a hook, not `lod`",
+            ),
+        ] {
+            let metadata = format!("[package.metadata.pluto]\n{options}\n");
+            let dir = package("invalid", &metadata, None);
+            match build(&dir) {
+                Err(err @ Error::Manifest { .. }) => {
+                    assert!(err.to_string().ends_with(message), "{err}")
+                }
+                Err(err) => panic!("expected an invalid manifest, got {err}"),
+                Ok(_) => panic!("expected an invalid manifest for {options}"),
+            }
+        }
+    }
+}
@@ -1,34 +1,34 @@
-use crate::Build;
+use crate::{Build, Hook, Sandbox};
 
 impl Build {
     /// If defined, Pluto errors will use ANSI color codes.
     pub fn opt_use_colored_output(&mut self) -> &mut Self {
-        self.0.define("PLUTO_USE_COLORED_OUTPUT", None);
+        self.config.define("PLUTO_USE_COLORED_OUTPUT", None);
         self
     }
 
     /// If defined, Pluto will exclude code snippets from error messages to make them shorter.
     pub fn opt_short_errors(&mut self) -> &mut Self {
-        self.0.define("PLUTO_SHORT_ERRORS", None);
+        self.config.define("PLUTO_SHORT_ERRORS", None);
         self
     }
 
     /// If defined, Pluto won't assume that source files are UTF-8 encoded and restrict valid symbol names.
     pub fn opt_no_utf8(&mut self) -> &mut Self {
-        self.0.define("PLUTO_NO_UTF8", None);
+        self.config.define("PLUTO_NO_UTF8", None);
         self
     }
 
     /// If defined, Pluto will use a jumptable in the VM even if not compiled via GCC or Clang.
     /// This will generally improve runtime performance but can add minutes to compile time, depending on the setup.
     pub fn opt_force_jumptable(&mut self) -> &mut Self {
-        self.0.define("PLUTO_FORCE_JUMPTABLE", None);
+        self.config.define("PLUTO_FORCE_JUMPTABLE", None);
         self
     }
 
     /// If defined, Pluto won't imbue tables with a metatable by default.
     pub fn opt_no_default_table_metatable(&mut self) -> &mut Self {
-        self.0.define("PLUTO_NO_DEFAULT_TABLE_METATABLE", None);
+        self.config.define("PLUTO_NO_DEFAULT_TABLE_METATABLE", None);
         self
     }
 
@@ -36,32 +36,32 @@ impl Build {
 
     /// If defined, the "global-shadow" warning is enabled by default.
     pub fn opt_warn_global_shadow(&mut self) -> &mut Self {
-        self.0.define("PLUTO_WARN_GLOBAL_SHADOW", None);
+        self.config.define("PLUTO_WARN_GLOBAL_SHADOW", None);
         self
     }
 
     /// The list of globals covered by the "global-shadow" warning.
     /// Default value: `"table","string","arg"`
     pub fn opt_common_global_names(&mut self, names: &str) -> &mut Self {
-        self.0.define("PLUTO_COMMON_GLOBAL_NAMES", Some(names));
+        self.config.define("PLUTO_COMMON_GLOBAL_NAMES", Some(names));
         self
     }
 
     /// If defined, the "non-portable-code" warning is enabled by default.
     pub fn opt_warn_non_portable_code(&mut self) -> &mut Self {
-        self.0.define("PLUTO_WARN_NON_PORTABLE_CODE", None);
+        self.config.define("PLUTO_WARN_NON_PORTABLE_CODE", None);
         self
     }
 
     /// If defined, the "non-portable-bytecode" warning is enabled by default.
     pub fn opt_warn_non_portable_bytecode(&mut self) -> &mut Self {
-        self.0.define("PLUTO_WARN_NON_PORTABLE_BYTECODE", None);
+        self.config.define("PLUTO_WARN_NON_PORTABLE_BYTECODE", None);
         self
     }
 
     /// If defined, the "non-portable-name" warning is enabled by default.
     pub fn opt_warn_non_portable_name(&mut self) -> &mut Self {
-        self.0.define("PLUTO_WARN_NON_PORTABLE_NAME", None);
+        self.config.define("PLUTO_WARN_NON_PORTABLE_NAME", None);
         self
     }
 
@@ -71,14 +71,14 @@ impl Build {
     /// So, for example, the `switch` keyword becomes `pluto_switch`. The `pluto_` variants are valid even if this is not defined.
     /// As of Pluto 0.7.0, scripts can individually set compatibility modes via `pluto_use`.
     pub fn opt_compatible_mode(&mut self) -> &mut Self {
-        self.0.define("PLUTO_COMPATIBLE_MODE", None);
+        self.config.define("PLUTO_COMPATIBLE_MODE", None);
         self
     }
 
     /// If defined, Pluto's automatic keyword detection will more aggressively disable keywords if they're not used exactly as expected.
     /// This will help when scripters use these keywords as globals across files or before their definition.
     pub fn opt_paranoid_keyword_detection(&mut self) -> &mut Self {
-        self.0.define("PLUTO_PARANOID_KEYWORD_DETECTION", None);
+        self.config.define("PLUTO_PARANOID_KEYWORD_DETECTION", None);
         self
     }
 
@@ -87,20 +87,20 @@ impl Build {
     /// If defined, Pluto will imply `pluto_use let` at the beginning of every script.
     /// Note that this keyword is deprecated as of 0.9.0.
     pub fn opt_use_let(&mut self) -> &mut Self {
-        self.0.define("PLUTO_USE_LET", None);
+        self.config.define("PLUTO_USE_LET", None);
         self
     }
 
     /// If defined, Pluto will imply `pluto_use const` at the beginning of every script.
     /// Note that this keyword is deprecated as of 0.9.0.
     pub fn opt_use_const(&mut self) -> &mut Self {
-        self.0.define("PLUTO_USE_CONST", None);
+        self.config.define("PLUTO_USE_CONST", None);
         self
     }
 
     /// If defined, Pluto will imply `pluto_use global` at the beginning of every script.
     pub fn opt_use_global(&mut self) -> &mut Self {
-        self.0.define("PLUTO_USE_GLOBAL", None);
+        self.config.define("PLUTO_USE_GLOBAL", None);
         self
     }
 
@@ -108,7 +108,7 @@ impl Build {
 
     /// If defined, Pluto will attempt to prevent infinite loops.
     pub fn opt_ilp_enabled(&mut self) -> &mut Self {
-        self.0.define("PLUTO_ILP_ENABLE", None);
+        self.config.define("PLUTO_ILP_ENABLE", None);
         self
     }
 
@@ -116,19 +116,21 @@ impl Build {
     /// If exceeded, the backward jump is ignored to escape the loop.
     pub fn opt_ilp_max_iterations(&mut self, iterations: u64) -> &mut Self {
         let s = iterations.to_string();
-        self.0.define("PLUTO_ILP_MAX_ITERATIONS", Some(s.as_str()));
+        self.config
+            .define("PLUTO_ILP_MAX_ITERATIONS", Some(s.as_str()));
         self
     }
 
     /// If you want (i.e) `luaB_next` to reset iteration counters, define as `luaB_next`.
     pub fn opt_ilp_hook_function(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_ILP_HOOK_FUNCTION", Some(function));
+        self.config
+            .define("PLUTO_ILP_HOOK_FUNCTION", Some(function));
         self
     }
 
     /// If defined, Pluto won't throw an error and instead just break out of the loop.
     pub fn opt_ilp_silent_break(&mut self) -> &mut Self {
-        self.0.define("PLUTO_ILP_SILENT_BREAK", None);
+        self.config.define("PLUTO_ILP_SILENT_BREAK", None);
         self
     }
 
@@ -138,7 +140,18 @@ impl Build {
     ///
     /// Default value: `luaG_runerror(L, "infinite loop detected (exceeded max iterations: %d)", PLUTO_ILP_MAX_ITERATIONS);`
     pub fn opt_ilp_error(&mut self, code: &str) -> &mut Self {
-        self.0.define("PLUTO_ILP_ERROR", Some(code));
+        self.config.define("PLUTO_ILP_ERROR", Some(code));
+        self
+    }
+
+    /// If defined, the maximum iterations and whether violations break silently are read from each state at runtime,
+    /// so they can be changed per state using `pluto_ffi::set_ilp!`.
+    /// [`opt_ilp_max_iterations`][Self::opt_ilp_max_iterations] and [`opt_ilp_silent_break`][Self::opt_ilp_silent_break] only provide the initial values.
+    ///
+    /// This implies [`opt_ilp_enabled`][Self::opt_ilp_enabled]. A custom [`opt_ilp_error`][Self::opt_ilp_error] is used as-is.
+    pub fn opt_ilp_runtime(&mut self) -> &mut Self {
+        self.opt_ilp_enabled();
+        self.config.define("PLUTOW_ILP_RUNTIME", None);
         self
     }
 
@@ -146,14 +159,14 @@ impl Build {
 
     /// If defined, Pluto will stop execution after a specified amount of time.
     pub fn opt_etl_enabled(&mut self) -> &mut Self {
-        self.0.define("PLUTO_ETL_ENABLE", None);
+        self.config.define("PLUTO_ETL_ENABLE", None);
         self
     }
 
     /// This is the maximum amount of nanoseconds the VM is allowed to run.
     pub fn opt_etl_nanos(&mut self, nanos: u64) -> &mut Self {
         let s = nanos.to_string();
-        self.0.define("PLUTO_ETL_NANOS", Some(s.as_str()));
+        self.config.define("PLUTO_ETL_NANOS", Some(s.as_str()));
         self
     }
 
@@ -162,16 +175,39 @@ impl Build {
     ///
     /// Default value: `luaG_runerror(L, "Execution time limit exceeded");`
     pub fn opt_etl_timesup(&mut self, code: &str) -> &mut Self {
-        self.0.define("PLUTO_ETL_TIMESUP", Some(code));
+        self.config.define("PLUTO_ETL_TIMESUP", Some(code));
+        self
+    }
+
+    /// If defined, the deadline can be set, extended and cleared per state at runtime using `pluto_ffi::set_etl_deadline!`.
+    /// States start without a deadline, unless [`opt_etl_nanos`][Self::opt_etl_nanos] is used.
+    ///
+    /// This implies [`opt_etl_enabled`][Self::opt_etl_enabled].
+    pub fn opt_etl_runtime(&mut self) -> &mut Self {
+        self.opt_etl_enabled();
+        self.config.define("PLUTOW_ETL_RUNTIME", None);
         self
     }
 
     // Memory Limit
 
-    /// If defined, constraints the amount of memory that the VM is allowed to use in MB (64000000 = 64 MB).
-    pub fn opt_memory_limit(&mut self, megabytes: u64) -> &mut Self {
-        let s = megabytes.to_string();
-        self.0.define("PLUTO_MEMORY_LIMIT", Some(s.as_str()));
+    /// If defined, constraints the amount of memory that the VM is allowed to use in bytes (64000000 = 64 MB).
+    ///
+    /// NOTE: Unless [`opt_memory_runtime`][Self::opt_memory_runtime] is used, this only applies to states created by `luaL_newstate`,
+    /// which doesn't include states created by `mlua`, since it uses its own allocator.
+    pub fn opt_memory_limit(&mut self, bytes: u64) -> &mut Self {
+        let s = bytes.to_string();
+        self.config.define("PLUTO_MEMORY_LIMIT", Some(s.as_str()));
+        self
+    }
+
+    /// If defined, the memory limit can be changed per state at runtime using `pluto_ffi::set_memory_limit!`,
+    /// and the current and peak memory usage can be queried using `pluto_ffi::get_memory_usage!`.
+    /// This applies to all states, regardless of their allocator.
+    ///
+    /// States start without a limit, unless [`opt_memory_limit`][Self::opt_memory_limit] is used.
+    pub fn opt_memory_runtime(&mut self) -> &mut Self {
+        self.config.define("PLUTOW_MEMORY_RUNTIME", None);
         self
     }
 
@@ -180,7 +216,7 @@ impl Build {
     /// If defined, Pluto will print every VM instruction that is ran.
     /// Note that you can modify lua_writestring to redirect output.
     pub fn opt_vmdump_enabled(&mut self) -> &mut Self {
-        self.0.define("PLUTO_VMDUMP", None);
+        self.config.define("PLUTO_VMDUMP", None);
         self
     }
 
@@ -189,7 +225,7 @@ impl Build {
     ///
     /// Valid opcodes: <https://github.com/PlutoLang/Pluto/blob/main/src/lopcodes.h#L197>
     pub fn opt_vmdump_ignore(&mut self, opcodes: &str) -> &mut Self {
-        self.0.define("vmDumpIgnore", Some(opcodes));
+        self.config.define("vmDumpIgnore", Some(opcodes));
         self
     }
 
@@ -200,13 +236,13 @@ impl Build {
     ///
     /// Valid opcodes: <https://github.com/PlutoLang/Pluto/blob/main/src/lopcodes.h#L197>
     pub fn opt_vmdump_allow(&mut self, opcodes: &str) -> &mut Self {
-        self.0.define("vmDumpAllow", Some(opcodes));
+        self.config.define("vmDumpAllow", Some(opcodes));
         self
     }
 
     /// If defined, Pluto will use [`opt_vmdump_allow`][Self::opt_vmdump_allow] instead of [`opt_vmdump_ignore`][Self::opt_vmdump_ignore].
     pub fn opt_vmdump_whitelist(&mut self) -> &mut Self {
-        self.0.define("PLUTO_VMDUMP_WHITELIST", None);
+        self.config.define("PLUTO_VMDUMP_WHITELIST", None);
         self
     }
 
@@ -214,7 +250,7 @@ impl Build {
     ///
     /// Default value: `true`
     pub fn opt_vmdump_cond(&mut self, code: &str) -> &mut Self {
-        self.0.define("PLUTO_VMDUMP_COND", Some(code));
+        self.config.define("PLUTO_VMDUMP_COND", Some(code));
         self
     }
 
@@ -222,7 +258,7 @@ impl Build {
 
     /// If defined, Pluto will not load compiled Lua or Pluto code.
     pub fn opt_disable_compiled(&mut self) -> &mut Self {
-        self.0.define("PLUTO_DISABLE_COMPILED", None);
+        self.config.define("PLUTO_DISABLE_COMPILED", None);
         self
     }
 
@@ -238,8 +274,10 @@ impl Build {
     /// }
     /// ```
     /// You can then call this function with the function name: `build.opt_load_hook("contmod_on_load")`.
+    ///
+    /// If you're using `pluto-ffi`, prefer [`opt_ffi_hook`][Self::opt_ffi_hook], which lets you install a typed Rust closure at runtime instead.
     pub fn opt_load_hook(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_LOAD_HOOK", Some(function));
+        self.config.define("PLUTO_LOAD_HOOK", Some(function));
         self
     }
 
@@ -248,7 +286,7 @@ impl Build {
     ///
     /// NOTE: Check the [`opt_load_hook`][Self::opt_load_hook] for an example of how to make your Rust hook callable from Pluto.
     pub fn opt_loadfile_hook(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_LOADFILE_HOOK", Some(function));
+        self.config.define("PLUTO_LOADFILE_HOOK", Some(function));
         self
     }
 
@@ -256,7 +294,7 @@ impl Build {
     /// Pluto currently offers no way to moderate code loaded like this,
     /// so you may define this to disable this method of code-loading.
     pub fn opt_disable_unmoderated_load(&mut self) -> &mut Self {
-        self.0.define("PLUTO_DISABLE_UNMODERATED_LOAD", None);
+        self.config.define("PLUTO_DISABLE_UNMODERATED_LOAD", None);
         self
     }
 
@@ -265,7 +303,7 @@ impl Build {
     ///
     /// NOTE: Check the [`opt_load_hook`][Self::opt_load_hook] for an example of how to make your Rust hook callable from Pluto.
     pub fn opt_loadclib_hook(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_LOADCLIB_HOOK", Some(function));
+        self.config.define("PLUTO_LOADCLIB_HOOK", Some(function));
         self
     }
 
@@ -274,19 +312,19 @@ impl Build {
     /// It's suggested you implement [`opt_loadclib_hook`][Self::opt_loadclib_hook], etc, for even more powerful coverage.
     /// package.loadlib can still load other Pluto/Lua libraries and use their lua_CFunction objects.
     pub fn opt_no_filesystem(&mut self) -> &mut Self {
-        self.0.define("PLUTO_NO_FILESYSTEM", None);
+        self.config.define("PLUTO_NO_FILESYSTEM", None);
         self
     }
 
     /// Disables os.execute & io.popen.
     pub fn opt_no_os_execute(&mut self) -> &mut Self {
-        self.0.define("PLUTO_NO_OS_EXECUTE", None);
+        self.config.define("PLUTO_NO_OS_EXECUTE", None);
         self
     }
 
     /// Eliminate any loading of any binaries. This removes package.loadlib and ffi.open and prevents 'require' from loading any C modules or shared libraries.
     pub fn opt_no_binaries(&mut self) -> &mut Self {
-        self.0.define("PLUTO_NO_BINARIES", None);
+        self.config.define("PLUTO_NO_BINARIES", None);
         self
     }
 
@@ -294,26 +332,32 @@ impl Build {
     ///
     /// Default value: `luaL_error(L, "binary modules cannot be loaded in this environment");`
     pub fn opt_no_binaries_fail(&mut self, code: &str) -> &mut Self {
-        self.0.define("PLUTO_NO_BINARIES_FAIL", Some(code));
+        self.config.define("PLUTO_NO_BINARIES_FAIL", Some(code));
         self
     }
 
     /// If defined, luaL_openlibs will not include the `debug` library.
     pub fn opt_no_debuglib(&mut self) -> &mut Self {
-        self.0.define("PLUTO_NO_DEBUGLIB", None);
+        self.config.define("PLUTO_NO_DEBUGLIB", None);
         self
     }
 
     /// If defined, luaL_openlibs will not include the `coroutine` library.
     pub fn opt_no_corolib(&mut self) -> &mut Self {
-        self.0.define("PLUTO_NO_COROLIB", None);
+        self.config.define("PLUTO_NO_COROLIB", None);
+        self
+    }
+
+    /// If defined, luaL_openlibs and `pluto_ffi::load_libraries!` will not include the `socket` library.
+    pub fn opt_no_socketlib(&mut self) -> &mut Self {
+        self.config.define("PLUTOW_NO_SOCKETLIB", None);
         self
     }
 
     /// If defined, all HTTP requests will fail.
     /// Note that the `socket` library can still be used to the same effect (with more effort).
     pub fn opt_disable_http_completely(&mut self) -> &mut Self {
-        self.0.define("PLUTO_DISABLE_HTTP_COMPLETELY", None);
+        self.config.define("PLUTO_DISABLE_HTTP_COMPLETELY", None);
         self
     }
 
@@ -323,7 +367,8 @@ impl Build {
     ///
     /// NOTE: Check the [`opt_load_hook`][Self::opt_load_hook] for an example of how to make your Rust hook callable from Pluto.
     pub fn opt_http_request_hook(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_HTTP_REQUEST_HOOK", Some(function));
+        self.config
+            .define("PLUTO_HTTP_REQUEST_HOOK", Some(function));
         self
     }
 
@@ -333,7 +378,7 @@ impl Build {
     ///
     /// NOTE: Check the [`opt_load_hook`][Self::opt_load_hook] for an example of how to make your Rust hook callable from Pluto.
     pub fn opt_read_file_hook(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_READ_FILE_HOOK", Some(function));
+        self.config.define("PLUTO_READ_FILE_HOOK", Some(function));
         self
     }
 
@@ -343,7 +388,7 @@ impl Build {
     ///
     /// NOTE: Check the [`opt_load_hook`][Self::opt_load_hook] for an example of how to make your Rust hook callable from Pluto.
     pub fn opt_write_file_hook(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_WRITE_FILE_HOOK", Some(function));
+        self.config.define("PLUTO_WRITE_FILE_HOOK", Some(function));
         self
     }
 
@@ -353,7 +398,43 @@ impl Build {
     ///
     /// NOTE: Check the [`opt_load_hook`][Self::opt_load_hook] for an example of how to make your Rust hook callable from Pluto.
     pub fn opt_ffi_call_hook(&mut self, function: &str) -> &mut Self {
-        self.0.define("PLUTO_FFI_CALL_HOOK", Some(function));
+        self.config.define("PLUTO_FFI_CALL_HOOK", Some(function));
+        self
+    }
+
+    /// Routes the given hook to the trampoline exported by `pluto-ffi`, so it can be implemented at runtime
+    /// with a typed Rust closure (see `pluto_ffi::hooks`) instead of a hand-written `extern "C"` function.
+    /// Hooks can be installed globally or per state using a policy; hooks without an installed closure allow everything.
+    ///
+    /// This replaces the corresponding `opt_*_hook` option, e.g. [`opt_load_hook`][Self::opt_load_hook] for [`Hook::Load`].
+    pub fn opt_ffi_hook(&mut self, hook: Hook) -> &mut Self {
+        self.config.define("PLUTOW_FFI_HOOKS", None);
+        self.config.define(hook.define(), Some(hook.trampoline()));
+        self
+    }
+
+    /// Routes all hooks to `pluto-ffi`. See [`opt_ffi_hook`][Self::opt_ffi_hook].
+    pub fn opt_ffi_hooks(&mut self) -> &mut Self {
+        for hook in Hook::ALL {
+            self.opt_ffi_hook(*hook);
+        }
+        self
+    }
+
+    /// Applies the content moderation options of the given sandbox profile. See [`Sandbox`] for what each profile disables.
+    pub fn opt_sandbox(&mut self, sandbox: Sandbox) -> &mut Self {
+        if sandbox == Sandbox::Trusted {
+            return self;
+        }
+        self.opt_no_filesystem()
+            .opt_no_os_execute()
+            .opt_no_binaries()
+            .opt_disable_compiled()
+            .opt_disable_unmoderated_load()
+            .opt_no_debuglib();
+        if sandbox == Sandbox::Strict {
+            self.opt_disable_http_completely().opt_no_socketlib();
+        }
         self
     }
 
@@ -361,13 +442,13 @@ impl Build {
 
     /// If defined, disables the length cache.
     pub fn opt_disable_length_cache(&mut self) -> &mut Self {
-        self.0.define("PLUTO_DISABLE_LENGTH_CACHE", None);
+        self.config.define("PLUTO_DISABLE_LENGTH_CACHE", None);
         self
     }
 
     /// If defined, disables table freezing.
     pub fn opt_disable_table_freezing(&mut self) -> &mut Self {
-        self.0.define("PLUTO_DISABLE_TABLE_FREEZING", None);
+        self.config.define("PLUTO_DISABLE_TABLE_FREEZING", None);
         self
     }
 }
@@ -0,0 +1,127 @@
+use crate::{Build, CpuBaseline, Error, Profile};
+
+impl Build {
+    /// Applies the `PLUTO_BUILD_*` environment variables, which take precedence over the options set in the build script,
+    /// so a one-off build can e.g. enable the VM dump without editing it. Empty variables are ignored.
+    ///
+    /// - `PLUTO_BUILD_DEFINES`: Pluto defines added to the options, separated by spaces,
+    ///   e.g. `PLUTO_VMDUMP PLUTO_ILP_ENABLE PLUTO_ILP_MAX_ITERATIONS=1000`.
+    /// - `PLUTO_BUILD_PROFILE`: `release`, `debug` or `rel_with_asserts`, see [`profile`][Self::profile].
+    /// - `PLUTO_BUILD_API_CHECK`: `1`/`true` or `0`/`false`, see [`api_check`][Self::api_check].
+    /// - `PLUTO_BUILD_CPU_BASELINE`: `portable`, `native` or a `-march`, see [`cpu_baseline`][Self::cpu_baseline].
+    /// - `PLUTO_BUILD_UNITY`: `1`/`true` or `0`/`false`, see [`unity`][Self::unity].
+    pub(crate) fn apply_env_overrides(&mut self) -> Result<(), Error> {
+        self.apply_overrides(|name| {
+            println!("cargo:rerun-if-env-changed={name}");
+            std::env::var(name).ok()
+        })
+    }
+
+    /// Applies the overrides read by `var`, see [`apply_env_overrides`][Self::apply_env_overrides].
+    fn apply_overrides(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), Error> {
+        let var = |name| var(name).filter(|value| !value.is_empty());
+        if let Some(defines) = var("PLUTO_BUILD_DEFINES") {
+            for define in defines.split_whitespace() {
+                match define.split_once('=') {
+                    Some((name, value)) => self.config.define(name, Some(value)),
+                    None => self.config.define(define, None),
+                }
+            }
+        }
+        if let Some(profile) = var("PLUTO_BUILD_PROFILE") {
+            self.profile = Some(match profile.as_str() {
+                "release" => Profile::Release,
+                "debug" => Profile::Debug,
+                "rel_with_asserts" => Profile::RelWithAsserts,
+                _ => {
+                    return Err(invalid(
+                        "PLUTO_BUILD_PROFILE",
+                        "`release`, `debug` or `rel_with_asserts`",
+                        &profile,
+                    ))
+                }
+            });
+        }
+        if let Some(api_check) = var("PLUTO_BUILD_API_CHECK") {
+            self.api_check = Some(flag("PLUTO_BUILD_API_CHECK", &api_check)?);
+        }
+        if let Some(baseline) = var("PLUTO_BUILD_CPU_BASELINE") {
+            self.cpu_baseline = match baseline.as_str() {
+                "portable" => CpuBaseline::Portable,
+                "native" => CpuBaseline::Native,
+                _ => CpuBaseline::March(baseline),
+            };
+        }
+        if let Some(unity) = var("PLUTO_BUILD_UNITY") {
+            self.unity = flag("PLUTO_BUILD_UNITY", &unity)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a boolean environment variable, `1`, `0`, `true` or `false` in any case.
+pub(crate) fn flag(name: &str, value: &str) -> Result<bool, Error> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(invalid(name, "`1`, `0`, `true` or `false`", value)),
+    }
+}
+
+fn invalid(name: &str, expected: &str, value: &str) -> Error {
+    Error::InvalidOption(format!("`{name}` must be {expected}, not `{value}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::config::Diagnostic;
+
+    fn apply(vars: &[(&str, &str)]) -> Result<Build, Error> {
+        let vars = vars.iter().copied().collect::<HashMap<_, _>>();
+        let mut build = Build::for_target("x86_64-unknown-linux-gnu".into())?;
+        build.apply_overrides(|name| vars.get(name).map(|value| value.to_string()))?;
+        Ok(build)
+    }
+
+    #[test]
+    fn defines_are_added() {
+        let build = apply(&[(
+            "PLUTO_BUILD_DEFINES",
+            "PLUTO_ILP_ENABLE PLUTO_ILP_SILENT_BREAK PLUTO_ILP_ERROR=error('too_many_iterations')",
+        )])
+        .unwrap();
+        assert!(build.config.is_defined("PLUTO_ILP_SILENT_BREAK"));
+        // contradicting options are reported before anything is compiled
+        assert!(matches!(
+            build.config.validate().as_slice(),
+            [Diagnostic::Error(message)] if message.contains("are mutually exclusive")
+        ));
+    }
+
+    #[test]
+    fn flags_take_booleans() {
+        for (value, unity) in [("1", true), ("true", true), ("FALSE", false), ("0", false)] {
+            assert_eq!(apply(&[("PLUTO_BUILD_UNITY", value)]).unwrap().unity, unity);
+        }
+        // empty variables are ignored
+        assert!(!apply(&[("PLUTO_BUILD_UNITY", "")]).unwrap().unity);
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        for (var, value) in [
+            ("PLUTO_BUILD_PROFILE", "fast"),
+            ("PLUTO_BUILD_API_CHECK", "yes"),
+            ("PLUTO_BUILD_UNITY", "on"),
+        ] {
+            match apply(&[(var, value)]) {
+                Err(Error::InvalidOption(message)) => assert!(message.contains(var), "{message}"),
+                Err(err) => panic!("expected {var}={value} to be invalid, got {err}"),
+                Ok(_) => panic!("expected {var}={value} to be invalid"),
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use crate::overrides::flag;
+use crate::Error;
+
+/// A Pluto library that was built beforehand, found using `PLUTO_LIB_DIR` or pkg-config.
+pub(crate) struct Prebuilt {
+    /// The library file, if known. It's used to verify the version and check for the `plutow_*` functions.
+    file: Option<PathBuf>,
+}
+
+impl Prebuilt {
+    /// Looks for a prebuilt Pluto of the given version and emits the cargo instructions to link it.
+    ///
+    /// If `PLUTO_LIB_DIR` is set, it must contain `libplutostatic.a` or `libpluto.so` (`plutostatic.lib` or `pluto.lib` on Windows).
+    /// `PLUTO_STATIC=1`/`true` or `PLUTO_STATIC=0`/`false` forces static or dynamic linking, otherwise the static library is preferred.
+    /// Returns `None` if Pluto should be compiled from source instead.
+    pub(crate) fn probe(version: &str) -> Result<Option<Self>, Error> {
+        println!("cargo:rerun-if-env-changed=PLUTO_LIB_DIR");
+        println!("cargo:rerun-if-env-changed=PLUTO_STATIC");
+        let statik = std::env::var("PLUTO_STATIC")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| flag("PLUTO_STATIC", &value))
+            .transpose()?;
+
+        if let Some(dir) = std::env::var_os("PLUTO_LIB_DIR") {
+            let prebuilt = Self::from_dir(Path::new(&dir), statik)?;
+            prebuilt.verify_version(version)?;
+            return Ok(Some(prebuilt));
+        }
+
+        #[cfg(feature = "pkg-config")]
+        if let Some(prebuilt) = Self::from_pkg_config(version, statik) {
+            prebuilt.verify_version(version)?;
+            return Ok(Some(prebuilt));
+        }
+
+        Ok(None)
+    }
+
+    fn from_dir(dir: &Path, statik: Option<bool>) -> Result<Self, Error> {
+        let candidates: &[(&str, bool)] = match statik {
+            Some(true) => &[("plutostatic", true)],
+            Some(false) => &[("pluto", false)],
+            None => &[("plutostatic", true), ("pluto", false)],
+        };
+        for &(name, statik) in candidates {
+            if let Some(file) = find_library(dir, name, statik) {
+                println!("cargo:rustc-link-search=native={}", dir.display());
+                if statik {
+                    println!("cargo:rustc-link-lib=static={name}");
+                    // libraries built by pluto-build keep Soup in separate archives
+                    for soup in ["soup", "soupintrin"] {
+                        if find_library(dir, soup, true).is_some() {
+                            println!("cargo:rustc-link-lib=static={soup}");
+                        }
+                    }
+                    if let Some(stdlib) = crate::cpp_stdlib() {
+                        println!("cargo:rustc-link-lib={stdlib}");
+                    }
+                } else {
+                    println!("cargo:rustc-link-lib=dylib={name}");
+                }
+                return Ok(Self { file: Some(file) });
+            }
+        }
+        Err(Error::LibraryNotFound(dir.to_owned()))
+    }
+
+    #[cfg(feature = "pkg-config")]
+    fn from_pkg_config(version: &str, statik: Option<bool>) -> Option<Self> {
+        let mut config = pkg_config::Config::new();
+        if let Some(statik) = statik {
+            config.statik(statik);
+        }
+        match config.exactly_version(version).probe("pluto") {
+            Ok(library) => {
+                let file = library.link_paths.iter().find_map(|dir| {
+                    find_library(dir, "plutostatic", true)
+                        .or_else(|| find_library(dir, "pluto", false))
+                });
+                Some(Self { file })
+            }
+            Err(err) => {
+                println!("cargo:warning=pluto-build: no Pluto {version} found by pkg-config, compiling it from source: {err}");
+                None
+            }
+        }
+    }
+
+    /// Checks that the library contains the `_PVERSION` string of the expected Pluto version.
+    fn verify_version(&self, version: &str) -> Result<(), Error> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let bytes = std::fs::read(file).map_err(|source| Error::Prebuilt {
+            path: file.clone(),
+            source,
+        })?;
+        let found = find_version(&bytes);
+        if found.as_deref() == Some(version) {
+            return Ok(());
+        }
+        Err(Error::VersionMismatch {
+            expected: version.to_owned(),
+            found,
+        })
+    }
+
+    /// Whether the library already contains the `plutow_*` functions used by `pluto-ffi`,
+    /// i.e. whether it was built by pluto-build.
+    ///
+    /// Returns [`Error::OutdatedPrebuilt`] if it was, but lacks some of the `functions` the options need,
+    /// because an older version of pluto-build built it. Linking would fail with undefined symbols otherwise.
+    pub(crate) fn has_wrapper(&self, functions: &[&str]) -> Result<bool, Error> {
+        let Some(file) = &self.file else {
+            return Ok(false);
+        };
+        let Ok(bytes) = std::fs::read(file) else {
+            return Ok(false);
+        };
+        if !contains(&bytes, b"plutow_openlibs") {
+            return Ok(false);
+        }
+        let missing = (functions.iter())
+            .filter(|function| !contains(&bytes, function.as_bytes()))
+            .map(|function| function.to_string())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::OutdatedPrebuilt {
+                path: file.clone(),
+                missing,
+            });
+        }
+        Ok(true)
+    }
+}
+
+fn find_library(dir: &Path, name: &str, statik: bool) -> Option<PathBuf> {
+    let files = if statik {
+        vec![format!("lib{name}.a"), format!("{name}.lib")]
+    } else {
+        vec![
+            format!("lib{name}.so"),
+            format!("lib{name}.dylib"),
+            format!("{name}.lib"),
+        ]
+    };
+    files
+        .into_iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.is_file())
+}
+
+/// Finds the `"Pluto x.y.z"` string literal, which is the value of `_PVERSION`.
+fn find_version(bytes: &[u8]) -> Option<String> {
+    const PREFIX: &[u8] = b"Pluto ";
+    (0..bytes.len()).find_map(|i| {
+        let version = bytes[i..].strip_prefix(PREFIX)?;
+        let len = version
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .count();
+        (len > 0 && version.get(len) == Some(&0))
+            .then(|| String::from_utf8_lossy(&version[..len]).into_owned())
+    })
+}
+
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|window| window == needle)
+}
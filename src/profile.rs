@@ -0,0 +1,40 @@
+/// How Pluto is optimized and which assertions it's compiled with. See [`Build::profile`][crate::Build::profile].
+///
+/// By default, Pluto follows the Cargo profile of the crate being built: the optimization level and debug info
+/// come from `OPT_LEVEL` and `DEBUG`, and assertions are enabled if the profile has `debug-assertions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Optimized, without assertions.
+    Release,
+    /// Unoptimized, with debug info, assertions and API checks.
+    Debug,
+    /// Optimized, but with assertions and API checks, e.g. to test a release build.
+    RelWithAsserts,
+}
+
+/// Applies `profile`, or the Cargo profile if there is none, to `build`.
+/// `api_check` overrides whether `LUA_USE_APICHECK` is defined, which defaults to whether assertions are enabled.
+pub(crate) fn apply(build: &mut cc::Build, profile: Option<Profile>, api_check: Option<bool>) {
+    let assertions = match profile {
+        Some(Profile::Release) => {
+            build.opt_level(3);
+            false
+        }
+        Some(Profile::Debug) => {
+            build.opt_level(0).debug(true);
+            true
+        }
+        Some(Profile::RelWithAsserts) => {
+            build.opt_level(3);
+            true
+        }
+        // cc already follows OPT_LEVEL and DEBUG
+        None => std::env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_some(),
+    };
+    if !assertions {
+        build.define("NDEBUG", None);
+    }
+    if api_check.unwrap_or(assertions) {
+        build.define("LUA_USE_APICHECK", None);
+    }
+}
@@ -0,0 +1,23 @@
+/// Vetted sets of content moderation options that can be applied using [`Build::opt_sandbox`][crate::Build::opt_sandbox].
+///
+/// Profiles only ever disable capabilities, so they can be combined with further `opt_*` options,
+/// e.g. hooks to moderate what's still allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sandbox {
+    /// For untrusted code that must not have any effect outside of the state.
+    ///
+    /// Disables the filesystem (`io`, `os.remove`, `os.rename`), `os.execute` and `io.popen`,
+    /// loading binaries (`package.loadlib`, `ffi.open`, C modules), loading compiled code,
+    /// loading code from reader functions, HTTP requests, the `socket` library and the `debug` library.
+    ///
+    /// NOTE: `require`, `dofile` and `loadfile` can still load scripts from disk.
+    /// Use [`Hook::LoadFile`][crate::Hook::LoadFile] to moderate them.
+    Strict,
+    /// Like [`Strict`][Self::Strict], but HTTP requests and the `socket` library are allowed.
+    ///
+    /// Use [`Hook::HttpRequest`][crate::Hook::HttpRequest] to moderate HTTP requests.
+    /// Sockets can't be moderated, so only use this profile if code may connect anywhere.
+    NetworkOnly,
+    /// For trusted code. Doesn't disable anything, so Pluto behaves like a regular build.
+    Trusted,
+}
@@ -0,0 +1,362 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{out_dir, Build, Error};
+
+/// The extensions of the scripts found in a directory.
+const EXTENSIONS: &[&str] = &["pluto", "lua"];
+
+/// The module generated into `OUT_DIR` by [`Build::precompile_scripts`].
+const MODULE: &str = "pluto_scripts.rs";
+
+/// The bundle packed into `OUT_DIR` by [`Build::bundle_scripts`].
+const BUNDLE: &str = "pluto_bundle.bin";
+
+impl Build {
+    /// Compiles the `.pluto` and `.lua` scripts in `dir` to bytecode and generates a Rust module embedding them.
+    ///
+    /// Panics if a script can't be compiled. See [`try_precompile_scripts`][Self::try_precompile_scripts].
+    pub fn precompile_scripts(&mut self, dir: impl AsRef<Path>) -> PathBuf {
+        self.try_precompile_scripts(dir)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Compiles the `.pluto` and `.lua` scripts in `dir` (and its subdirectories) to bytecode
+    /// and generates a Rust module embedding them, which is returned.
+    ///
+    /// The scripts are compiled with `plutoc` built by [`compile_binaries`][Self::compile_binaries], so with the same options,
+    /// and Pluto's warnings are reported like with [`check_scripts`][Self::check_scripts].
+    /// The module contains a `&[u8]` constant per script, named after its path (`util/json.pluto` becomes `UTIL_JSON`),
+    /// and `SCRIPTS`, a list of all scripts by module name (`util.json`). Include it with:
+    ///
+    /// ```rust,ignore
+    /// include!(concat!(env!("OUT_DIR"), "/pluto_scripts.rs"));
+    /// ```
+    ///
+    /// Returns [`Error::InvalidOption`] with [`opt_disable_compiled`][Self::opt_disable_compiled], since Pluto wouldn't load the bytecode.
+    /// When cross-compiling, `plutoc` can't run on the host, so the sources are embedded instead. This is only reported
+    /// as a cargo warning, and the scripts aren't checked, so syntax errors only show up when they're loaded.
+    pub fn try_precompile_scripts(&mut self, dir: impl AsRef<Path>) -> Result<PathBuf, Error> {
+        self.apply_env_overrides()?;
+        if self.config.is_defined("PLUTO_DISABLE_COMPILED") {
+            return Err(Error::InvalidOption(
+                "scripts can't be precompiled with `opt_disable_compiled`, Pluto won't load bytecode"
+                    .into(),
+            ));
+        }
+
+        let dir = dir.as_ref();
+        println!("cargo:rerun-if-changed={}", dir.display());
+        let scripts = find_scripts(dir)?;
+        let plutoc = self.host_plutoc("embedding the sources of the scripts instead")?;
+
+        let out_dir = out_dir()?.join("pluto_scripts");
+        let mut consts = HashMap::new();
+        let mut entries = Vec::new();
+        let mut module = format!("// Generated by pluto-build from {}.\n", dir.display());
+        for script in &scripts {
+            let name = const_name(script);
+            let module_name = module_name(script);
+            if let Some(other) = consts.insert(name.clone(), module_name.clone()) {
+                return Err(Error::InvalidOption(format!(
+                    "`{module_name}` and `{other}` are both embedded as `{name}`"
+                )));
+            }
+
+            let output = match &plutoc {
+                Some(plutoc) => {
+                    let output = out_dir.join(script).with_extension("out");
+                    compile_script(plutoc, dir, script, &output, &mut self.reported_scripts)?;
+                    output
+                }
+                // `include_bytes!` needs absolute paths. Build scripts run in the directory of the crate
+                None => std::env::current_dir()
+                    .map(|cwd| cwd.join(dir).join(script))
+                    .unwrap_or_else(|_| dir.join(script)),
+            };
+            writeln!(module, "\n#[allow(dead_code)]").unwrap();
+            writeln!(
+                module,
+                "pub const {name}: &[u8] = include_bytes!({output:?});"
+            )
+            .unwrap();
+            entries.push((module_name, name));
+        }
+
+        module.push_str(
+            "\n/// All scripts by module name, e.g. to register them in `package.preload`.\n",
+        );
+        module.push_str("#[allow(dead_code)]\n");
+        module.push_str("pub const SCRIPTS: &[(&str, &[u8])] = &[\n");
+        for (module_name, name) in &entries {
+            writeln!(module, "    ({module_name:?}, {name}),").unwrap();
+        }
+        module.push_str("];\n");
+
+        let path = out_dir.with_file_name(MODULE);
+        std::fs::write(&path, module).map_err(|source| Error::Output {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(path)
+    }
+
+    /// Checks the syntax of the `.pluto` and `.lua` scripts in `dir` and reports Pluto's warnings as cargo warnings.
+    ///
+    /// Panics if a script has a syntax error. See [`try_check_scripts`][Self::try_check_scripts].
+    pub fn check_scripts(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.try_check_scripts(dir)
+            .unwrap_or_else(|err| panic!("{err}"));
+        self
+    }
+
+    /// Checks the syntax of the `.pluto` and `.lua` scripts in `dir` (and its subdirectories)
+    /// and reports Pluto's warnings as cargo warnings, e.g. `scripts/main.pluto:3: warning: duplicate local declaration [var-shadow]`.
+    ///
+    /// The scripts are parsed by `plutoc` built by [`compile_binaries`][Self::compile_binaries],
+    /// so the `opt_warn_*` options apply, as do `@pluto_warnings` comments in the scripts.
+    /// Syntax errors, including warnings made fatal, are returned as [`Error::Script`].
+    /// When cross-compiling, `plutoc` can't run on the host, so the scripts aren't checked, which is only reported as a cargo warning.
+    pub fn try_check_scripts(&mut self, dir: impl AsRef<Path>) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        println!("cargo:rerun-if-changed={}", dir.display());
+        let scripts = find_scripts(dir)?;
+        let Some(plutoc) = self.host_plutoc("the scripts aren't checked")? else {
+            return Ok(());
+        };
+        for script in &scripts {
+            run_plutoc(
+                &plutoc,
+                dir,
+                script,
+                &["-p".as_ref()],
+                &mut self.reported_scripts,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Packs the `.pluto` and `.lua` scripts in `dir` into a compressed bundle, which pluto-ffi serves to `require`.
+    ///
+    /// Panics if a script can't be compiled. See [`try_bundle_scripts`][Self::try_bundle_scripts].
+    pub fn bundle_scripts(&mut self, dir: impl AsRef<Path>) -> PathBuf {
+        self.try_bundle_scripts(dir)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Packs the `.pluto` and `.lua` scripts in `dir` (and its subdirectories) into a compressed bundle,
+    /// which is returned. Install it with pluto-ffi's `install_bundle!`, then `require("util.json")`
+    /// loads `util/json.pluto` from the bundle, and `require("util")` loads `util/init.pluto`.
+    ///
+    /// The scripts are precompiled like with [`precompile_scripts`][Self::precompile_scripts],
+    /// unless Pluto won't load bytecode because of [`opt_disable_compiled`][Self::opt_disable_compiled]
+    /// or `plutoc` can't run because of cross-compiling. Then their sources are bundled instead.
+    /// When cross-compiling, this is only reported as a cargo warning, and the scripts aren't checked,
+    /// so syntax errors only show up when they're loaded.
+    pub fn try_bundle_scripts(&mut self, dir: impl AsRef<Path>) -> Result<PathBuf, Error> {
+        let dir = dir.as_ref();
+        println!("cargo:rerun-if-changed={}", dir.display());
+        let scripts = find_scripts(dir)?;
+        self.apply_env_overrides()?;
+        let compiled = !self.config.is_defined("PLUTO_DISABLE_COMPILED");
+        let plutoc = self.host_plutoc(match compiled {
+            true => "bundling the sources of the scripts instead",
+            false => "the scripts aren't checked",
+        })?;
+
+        let out_dir = out_dir()?.join("pluto_bundle");
+        let mut modules = HashMap::new();
+        let mut bundle = Vec::new();
+        for script in &scripts {
+            let name = module_name(script);
+            if let Some(other) = modules.insert(name.clone(), script) {
+                return Err(Error::InvalidOption(format!(
+                    "`{}` and `{}` are both bundled as `{name}`",
+                    other.display(),
+                    script.display()
+                )));
+            }
+
+            let code = match &plutoc {
+                Some(plutoc) if compiled => {
+                    let output = out_dir.join(script).with_extension("out");
+                    compile_script(plutoc, dir, script, &output, &mut self.reported_scripts)?;
+                    read(&output)?
+                }
+                Some(plutoc) => {
+                    run_plutoc(
+                        plutoc,
+                        dir,
+                        script,
+                        &["-p".as_ref()],
+                        &mut self.reported_scripts,
+                    )?;
+                    read(&dir.join(script))?
+                }
+                None => read(&dir.join(script))?,
+            };
+            // see patches/bundle.cpp for the format
+            let path = script.to_string_lossy().replace('\\', "/");
+            for field in [name.as_bytes(), path.as_bytes(), &code] {
+                bundle.extend_from_slice(&(field.len() as u32).to_le_bytes());
+                bundle.extend_from_slice(field);
+            }
+        }
+
+        let path = out_dir.with_file_name(BUNDLE);
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&bundle, 9);
+        std::fs::write(&path, compressed).map_err(|source| Error::Output {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(path)
+    }
+
+    /// The `plutoc` to run on the host, which isn't available when cross-compiling.
+    fn host_plutoc(&mut self, fallback: &str) -> Result<Option<PathBuf>, Error> {
+        let host = std::env::var("HOST").map_err(|_| Error::MissingEnv("HOST"))?;
+        if host != self.target {
+            println!(
+                "cargo:warning=pluto-build: can't run plutoc when cross-compiling, {fallback}"
+            );
+            return Ok(None);
+        }
+        Ok(Some(self.try_compile_binaries()?.plutoc))
+    }
+}
+
+/// Finds the scripts in `dir` and its subdirectories, relative to `dir`.
+fn find_scripts(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut scripts = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(subdir) = dirs.pop() {
+        let entries =
+            std::fs::read_dir(dir.join(&subdir)).map_err(|source| Error::MissingSources {
+                path: dir.join(&subdir),
+                source,
+            })?;
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = subdir.join(entry.file_name());
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext == *e))
+            {
+                scripts.push(path);
+            }
+        }
+    }
+    scripts.sort();
+    Ok(scripts)
+}
+
+fn compile_script(
+    plutoc: &Path,
+    dir: &Path,
+    script: &Path,
+    output: &Path,
+    reported: &mut HashSet<PathBuf>,
+) -> Result<(), Error> {
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent).map_err(|source| Error::Output {
+            path: parent.to_owned(),
+            source,
+        })?;
+    }
+    run_plutoc(
+        plutoc,
+        dir,
+        script,
+        &["-o".as_ref(), output.as_os_str()],
+        reported,
+    )
+}
+
+/// Runs `plutoc` on `script`, forwarding its warnings as cargo warnings unless they're in `reported` already.
+fn run_plutoc(
+    plutoc: &Path,
+    dir: &Path,
+    script: &Path,
+    args: &[&OsStr],
+    reported: &mut HashSet<PathBuf>,
+) -> Result<(), Error> {
+    let path = dir.join(script);
+    // the chunk name is the path relative to `dir`, so it doesn't depend on where the crate is built
+    let output = Command::new(plutoc)
+        .args(args)
+        .arg(script)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| Error::Script {
+            path: path.clone(),
+            message: format!("failed to run plutoc: {err}"),
+        })?;
+
+    // messages start with the chunk name, report them relative to the crate instead
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let prefix = format!("{}: ", plutoc.display());
+    let stderr = stderr.strip_prefix(&prefix).unwrap_or(&stderr);
+    let chunk_name = script.to_string_lossy();
+    let stderr = stderr
+        .lines()
+        .map(|line| match line.starts_with(' ') {
+            // the source code and notes below the message
+            true => line.to_owned(),
+            false => line.replacen(&*chunk_name, &path.to_string_lossy(), 1),
+        })
+        .collect::<Vec<_>>();
+
+    if !output.status.success() {
+        return Err(Error::Script {
+            path,
+            message: stderr.join("\n"),
+        });
+    }
+    // the same script is parsed again by every `*_scripts` method
+    if reported.insert(path) {
+        for line in stderr {
+            println!("cargo:warning={line}");
+        }
+    }
+    Ok(())
+}
+
+/// `util/json.pluto` becomes `util.json`, and `util/init.pluto` becomes `util`.
+fn module_name(script: &Path) -> String {
+    let script = script.with_extension("");
+    let mut parts = script
+        .iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>();
+    if parts.len() > 1 && parts.last().is_some_and(|part| part == "init") {
+        parts.pop();
+    }
+    parts.join(".")
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|err| Error::Script {
+        path: path.to_owned(),
+        message: err.to_string(),
+    })
+}
+
+/// `util/json.pluto` becomes `UTIL_JSON`.
+fn const_name(script: &Path) -> String {
+    let name = module_name(script)
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect::<String>();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// Finds the Soup sources that `roots` need, so the rest of Soup isn't compiled.
+///
+/// Follows the headers the preprocessor includes with the flags and defines of `build`, so libraries left out
+/// of the build don't pull in their part of Soup. Every Soup header is assumed to be implemented by the source
+/// with the same name, and the includes of that source are followed too.
+///
+/// Returns `None` if the compiler can't list the includes, then all of Soup has to be compiled.
+pub(crate) fn needed_sources(
+    build: &cc::Build,
+    soup_dir: &Path,
+    roots: Vec<PathBuf>,
+) -> Result<Option<Vec<PathBuf>>, Error> {
+    let compiler = build.try_get_compiler()?;
+    if compiler.is_like_msvc() {
+        return Ok(None);
+    }
+    let soup_dir = canonical(soup_dir);
+    let mut needed = BTreeSet::new();
+    let mut pending = roots;
+    while !pending.is_empty() {
+        let output = (compiler.to_command()).arg("-MM").args(&pending).output();
+        let Some(deps) = output.ok().filter(|output| output.status.success()) else {
+            return Ok(None);
+        };
+        pending.clear();
+        for header in dependencies(&String::from_utf8_lossy(&deps.stdout)) {
+            let header = canonical(&header);
+            if header.parent() != Some(&soup_dir) {
+                continue;
+            }
+            let source = header.with_extension("cpp");
+            if source.exists() && needed.insert(source.clone()) {
+                pending.push(source);
+            }
+        }
+    }
+    Ok(Some(needed.into_iter().collect()))
+}
+
+/// The prerequisites of the make rules printed by `-MM`.
+fn dependencies(rules: &str) -> Vec<PathBuf> {
+    // file names with spaces are escaped, lines are continued with backslashes
+    let rules = rules.replace("\\ ", "\0").replace("\\\n", " ");
+    (rules.split_whitespace())
+        .filter(|word| !word.ends_with(':'))
+        .map(|word| PathBuf::from(word.replace('\0', " ")))
+        .collect()
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// What a source file declares that could clash with another source in the same translation unit.
+struct Source<'a> {
+    path: &'a Path,
+    size: usize,
+    identifiers: BTreeSet<&'a str>,
+    /// Macros defined by the source, which stay defined for the sources after it.
+    macros: BTreeMap<&'a str, &'a str>,
+    /// Names declared `static`, which can only be declared once per translation unit.
+    statics: BTreeSet<&'a str>,
+}
+
+impl<'a> Source<'a> {
+    fn parse(path: &'a Path, text: &'a str) -> Self {
+        let identifiers = (text.split(|c: char| !c.is_ascii_alphanumeric() && c != '_'))
+            .filter(|word| word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+            .collect();
+        let mut macros = BTreeMap::new();
+        let mut statics = BTreeSet::new();
+        for line in text.lines().map(str::trim) {
+            if let Some(define) = (line.strip_prefix('#'))
+                .and_then(|line| line.trim_start().strip_prefix("define"))
+                .filter(|define| define.starts_with(char::is_whitespace))
+            {
+                let define = define.trim_start();
+                let end = define
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(define.len());
+                macros.insert(&define[..end], define[end..].trim());
+            } else if let Some(declaration) = line.strip_prefix("static ") {
+                // the name is the last word before the parameters, array size, initializer or body
+                let Some(end) = declaration.find(['(', '[', '=', ';', '{']) else {
+                    continue;
+                };
+                let name = declaration[..end]
+                    .rsplit(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .find(|word| !word.is_empty());
+                statics.extend(name);
+            }
+        }
+        Self {
+            path,
+            size: text.len(),
+            identifiers,
+            macros,
+            statics,
+        }
+    }
+
+    fn clashes_with(&self, other: &Source) -> bool {
+        let leaks = |a: &Source, b: &Source| {
+            (a.macros.iter()).any(|(name, value)| {
+                b.identifiers.contains(name) && b.macros.get(name) != Some(value)
+            })
+        };
+        !self.statics.is_disjoint(&other.statics) || leaks(self, other) || leaks(other, self)
+    }
+}
+
+/// Amalgamates `sources` into a few translation units `{name}_{n}.cpp` in `dir`, each including some of them,
+/// and returns the units. Sources that would clash in one unit are put into different units:
+/// those declaring the same `static` name, and those using a name another one defines as a macro.
+pub(crate) fn amalgamate<P: AsRef<Path>>(
+    sources: &[P],
+    dir: &Path,
+    name: &str,
+) -> Result<Vec<PathBuf>, Error> {
+    let texts = (sources.iter())
+        .map(|path| {
+            let path = path.as_ref();
+            std::fs::read_to_string(path).map_err(|source| Error::MissingSources {
+                path: path.to_owned(),
+                source,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut sources = (sources.iter().zip(&texts))
+        .map(|(path, text)| Source::parse(path.as_ref(), text))
+        .collect::<Vec<_>>();
+
+    // the largest sources first, each into the smallest unit it doesn't clash with
+    sources.sort_by_key(|source| std::cmp::Reverse(source.size));
+    let mut units: Vec<Vec<&Source>> = (0..jobs()).map(|_| Vec::new()).collect();
+    for source in &sources {
+        let unit = (units.iter_mut())
+            .filter(|unit| unit.iter().all(|other| !source.clashes_with(other)))
+            .min_by_key(|unit| unit.iter().map(|other| other.size).sum::<usize>());
+        match unit {
+            Some(unit) => unit.push(source),
+            None => units.push(vec![source]),
+        }
+    }
+
+    std::fs::create_dir_all(dir).map_err(|source| Error::Output {
+        path: dir.to_owned(),
+        source,
+    })?;
+    let mut paths = Vec::new();
+    for (n, unit) in units.iter().filter(|unit| !unit.is_empty()).enumerate() {
+        let path = dir.join(format!("{name}_{n}.cpp"));
+        let mut code = String::new();
+        for source in unit {
+            // backslashes of Windows paths could be taken as escapes
+            let include = source.path.display().to_string().replace('\\', "/");
+            code += &format!("#include \"{include}\"\n");
+        }
+        std::fs::write(&path, code).map_err(|source| Error::Output {
+            path: path.clone(),
+            source,
+        })?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// The number of units to compile in parallel, a handful so each unit still saves parsing headers.
+fn jobs() -> usize {
+    let jobs = std::env::var("NUM_JOBS")
+        .ok()
+        .and_then(|jobs| jobs.parse().ok());
+    jobs.unwrap_or(4).clamp(2, 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let text = "#define LUA_CORE\n# define lua_c(L) luaL_check(L)\nstatic int counter = 0;\n\
+                    static const char *const names[] = {};\nstatic void step (lua_State *L) {\n";
+        let source = Source::parse(Path::new("lvm.cpp"), text);
+        assert_eq!(
+            source.macros.into_iter().collect::<Vec<_>>(),
+            [("LUA_CORE", ""), ("lua_c", "(L) luaL_check(L)")]
+        );
+        assert_eq!(
+            source.statics.into_iter().collect::<Vec<_>>(),
+            ["counter", "names", "step"]
+        );
+        assert!(source.identifiers.contains("lua_State"));
+    }
+
+    #[test]
+    fn clashes() {
+        let parse = |text| Source::parse(Path::new("a.cpp"), text);
+        let a = parse("static int next (void);\n");
+        assert!(a.clashes_with(&parse("static int next = 1;\n")));
+        assert!(!a.clashes_with(&parse("static int prev = 1;\n")));
+        // a macro leaks into the sources after it, unless they define it the same way
+        let macro_ = parse("#define next(x) x + 1\n");
+        assert!(macro_.clashes_with(&parse("int f (int next) { return next; }\n")));
+        assert!(!macro_.clashes_with(&parse("#define next(x) x + 1\nint y = next(1);\n")));
+        assert!(!macro_.clashes_with(&parse("int prev;\n")));
+    }
+
+    #[test]
+    fn amalgamate_separates_clashing_sources() {
+        let dir = crate::test_dir("unity");
+        let sources = [
+            ("lapi.cpp", "static int index2value (void) { return 0; }\n"),
+            (
+                "ltable.cpp",
+                "static int index2value (void) { return 1; }\n",
+            ),
+            ("lstrlib.cpp", "#define L_FMTFLAGSF \"-+ #0\"\n"),
+            ("lvm.cpp", "int L_FMTFLAGSF;\n"),
+            ("lcode.cpp", "int luaK_code;\n"),
+        ]
+        .map(|(file, text)| {
+            let path = dir.join(file);
+            std::fs::write(&path, text).unwrap();
+            path
+        });
+        let units = amalgamate(&sources, &dir.join("unity"), "pluto").unwrap();
+
+        let includes = (units.iter())
+            .map(|unit| std::fs::read_to_string(unit).unwrap())
+            .collect::<Vec<_>>();
+        let unit_of = |file: &str| {
+            let path = dir.join(file).display().to_string().replace('\\', "/");
+            let include = format!("#include \"{path}\"\n");
+            let units = (includes.iter().enumerate())
+                .filter(|(_, code)| code.contains(&include))
+                .map(|(n, _)| n)
+                .collect::<Vec<_>>();
+            assert_eq!(units.len(), 1, "{file} must be included exactly once");
+            units[0]
+        };
+        for (n, unit) in units.iter().enumerate() {
+            assert_eq!(*unit, dir.join("unity").join(format!("pluto_{n}.cpp")));
+        }
+        assert_ne!(unit_of("lapi.cpp"), unit_of("ltable.cpp"));
+        assert_ne!(unit_of("lstrlib.cpp"), unit_of("lvm.cpp"));
+        unit_of("lcode.cpp");
+        assert_eq!(
+            includes
+                .iter()
+                .map(|code| code.lines().count())
+                .sum::<usize>(),
+            5
+        );
+    }
+
+    #[test]
+    fn missing_sources_are_errors() {
+        let dir = crate::test_dir("unity-missing");
+        let result = amalgamate(&[dir.join("lvm.cpp")], &dir, "pluto");
+        assert!(matches!(result, Err(Error::MissingSources { .. })));
+    }
+}
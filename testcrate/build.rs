@@ -1,9 +1,37 @@
 use pluto_build as pluto;
 
 fn main() {
-    println!("cargo:rerun-if-changed=build.rs");
-    pluto::Build::new()
-        .opt_ilp_enabled()
-        .opt_load_hook("contmod_on_load")
-        .compile();
+    let strict = std::env::var_os("CARGO_FEATURE_SANDBOX_STRICT").is_some();
+    let network_only = std::env::var_os("CARGO_FEATURE_SANDBOX_NETWORK_ONLY").is_some();
+    let trusted = !strict && !network_only;
+    let sandbox = match (strict, network_only) {
+        (false, false) => pluto::Sandbox::Trusted,
+        (true, false) => pluto::Sandbox::Strict,
+        (false, true) => pluto::Sandbox::NetworkOnly,
+        (true, true) => panic!("only one sandbox feature can be enabled at a time"),
+    };
+    let shared = std::env::var_os("CARGO_FEATURE_SHARED").is_some();
+    let unity = std::env::var_os("CARGO_FEATURE_UNITY").is_some();
+    let mut build = pluto::Build::new();
+    build
+        .shared(shared)
+        .unity(unity)
+        .opt_sandbox(sandbox)
+        .opt_ilp_runtime()
+        .opt_etl_runtime()
+        .opt_memory_runtime();
+    if !shared {
+        build.opt_ffi_hook(pluto::Hook::Load);
+    }
+    build.compile();
+
+    let binaries = build.compile_binaries();
+    println!("cargo:rustc-env=PLUTO_BIN={}", binaries.pluto.display());
+
+    build.check_scripts("scripts");
+    build.bundle_scripts("scripts");
+    // the sandbox profiles disable compiled code
+    if trusted {
+        build.precompile_scripts("scripts");
+    }
 }
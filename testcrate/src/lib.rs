@@ -1,15 +1,10 @@
-#[no_mangle]
-pub extern "C" fn contmod_on_load(
-    _lua: *mut mlua::ffi::lua_State,
-    code: *const std::os::raw::c_char,
-) -> bool {
-    let code = unsafe { std::ffi::CStr::from_ptr(code) }.to_str().unwrap();
-    println!("contmod_on_load: {code}");
-    false
-}
-
 #[test]
+#[cfg(not(feature = "shared"))]
 fn test_pluto_hook() {
+    pluto_ffi::hooks::set_load_hook(|code: &str| {
+        println!("load hook: {code}");
+        false
+    });
     let lua = mlua::Lua::new();
     match lua
         .load(r#"load("os.exit()")"#)
@@ -20,6 +15,46 @@ fn test_pluto_hook() {
         Err(mlua::Error::RuntimeError(e)) => println!("hook catched: {e}"),
         Err(e) => panic!("unexpected error: {e}"),
     }
+
+    // a hook can replace the global hooks while it runs
+    pluto_ffi::hooks::set_load_hook(|_: &str| {
+        pluto_ffi::hooks::set_load_hook(|_: &str| false);
+        true
+    });
+    lua.load(r#"load("return 1")"#).exec().unwrap();
+    assert!(lua.load(r#"load("return 1")"#).exec().is_err());
+}
+
+#[test]
+#[cfg(not(feature = "shared"))]
+fn test_pluto_policy() {
+    let trusted = mlua::Lua::new();
+    pluto_ffi::set_policy!(
+        &trusted,
+        pluto_ffi::hooks::Policy::new().load(|_: &str| true)
+    )
+    .unwrap();
+    let untrusted = mlua::Lua::new();
+    pluto_ffi::set_policy!(
+        &untrusted,
+        pluto_ffi::hooks::Policy::new().load(|_: &str| false)
+    )
+    .unwrap();
+
+    let code = r#"load("return 1")"#;
+    match trusted.load(code).set_name("trusted policy test").exec() {
+        Ok(_) => {}
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+    match untrusted
+        .load(code)
+        .set_name("untrusted policy test")
+        .exec()
+    {
+        Ok(_) => panic!("should have errored"),
+        Err(mlua::Error::RuntimeError(e)) => println!("policy catched: {e}"),
+        Err(e) => panic!("unexpected error: {e}"),
+    }
 }
 
 #[test]
@@ -32,6 +67,124 @@ fn test_pluto_ilp() {
     }
 }
 
+#[test]
+fn test_pluto_ilp_runtime() {
+    let strict = mlua::Lua::new();
+    pluto_ffi::set_ilp!(
+        &strict,
+        pluto_ffi::limits::Ilp {
+            max_iterations: 10,
+            silent_break: false,
+        }
+    )
+    .unwrap();
+    let lenient = mlua::Lua::new();
+    pluto_ffi::set_ilp!(
+        &lenient,
+        pluto_ffi::limits::Ilp {
+            max_iterations: 100,
+            silent_break: true,
+        }
+    )
+    .unwrap();
+    assert_eq!(pluto_ffi::get_ilp!(&lenient).unwrap().max_iterations, 100);
+
+    let code = "local i = 0 while true do i += 1 end return i";
+    match strict.load(code).set_name("strict ilp test").exec() {
+        Ok(_) => panic!("should have errored"),
+        Err(mlua::Error::RuntimeError(e)) => println!("ilp catched: {e}"),
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+    match lenient
+        .load(code)
+        .set_name("lenient ilp test")
+        .eval::<i64>()
+    {
+        Ok(i) => assert_eq!(i, 100),
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+    // a silent break doesn't cut short the loops after it
+    let code =
+        "local a = 0 while true do a += 1 end local b = 0 while true do b += 1 end return a, b";
+    assert_eq!(lenient.load(code).eval::<(i64, i64)>().unwrap(), (100, 100));
+
+    let zero = pluto_ffi::limits::Ilp {
+        max_iterations: 0,
+        silent_break: true,
+    };
+    assert!(pluto_ffi::set_ilp!(&lenient, zero).is_err());
+    assert_eq!(pluto_ffi::get_ilp!(&lenient).unwrap().max_iterations, 100);
+}
+
+#[test]
+fn test_pluto_etl_runtime() {
+    use std::time::Duration;
+
+    let lua = mlua::Lua::new();
+    assert_eq!(pluto_ffi::get_etl_deadline!(&lua).unwrap(), None);
+    // make sure ILP doesn't kick in first
+    pluto_ffi::set_ilp!(
+        &lua,
+        pluto_ffi::limits::Ilp {
+            max_iterations: u32::MAX,
+            silent_break: false,
+        }
+    )
+    .unwrap();
+
+    pluto_ffi::set_etl_deadline!(&lua, Some(Duration::from_millis(5))).unwrap();
+    match lua.load("while true do end").set_name("etl test").exec() {
+        Ok(_) => panic!("should have errored"),
+        Err(mlua::Error::RuntimeError(e)) => assert!(e.contains("Execution time limit exceeded")),
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+    assert_eq!(
+        pluto_ffi::get_etl_deadline!(&lua).unwrap(),
+        Some(Duration::ZERO)
+    );
+
+    pluto_ffi::extend_etl_deadline!(&lua, Duration::from_secs(5)).unwrap();
+    lua.load("for i = 1, 10 do end").exec().unwrap();
+    pluto_ffi::set_etl_deadline!(&lua, None).unwrap();
+    assert_eq!(pluto_ffi::get_etl_deadline!(&lua).unwrap(), None);
+}
+
+#[test]
+fn test_pluto_memory_runtime() {
+    let lua = mlua::Lua::new();
+    let usage = pluto_ffi::get_memory_usage!(&lua).unwrap();
+    assert_eq!(usage.limit, None);
+    assert!(usage.peak >= usage.current);
+
+    let limit = usage.current + 256 * 1024;
+    pluto_ffi::set_memory_limit!(&lua, Some(limit)).unwrap();
+    match lua
+        .load("local t = {} for i = 1, 1000000 do t[i] = tostring(i) end")
+        .exec()
+    {
+        Ok(_) => panic!("should have errored"),
+        Err(mlua::Error::MemoryError(_)) => {}
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+    lua.gc_collect().unwrap();
+    let usage = pluto_ffi::get_memory_usage!(&lua).unwrap();
+    assert_eq!(usage.limit, Some(limit));
+    assert!(usage.peak <= limit && usage.peak > usage.current);
+
+    pluto_ffi::reset_memory_peak!(&lua).unwrap();
+    let usage = pluto_ffi::get_memory_usage!(&lua).unwrap();
+    assert_eq!(usage.peak, usage.current);
+
+    // limits are per state
+    let other = mlua::Lua::new();
+    assert_eq!(pluto_ffi::get_memory_usage!(&other).unwrap().limit, None);
+
+    pluto_ffi::set_memory_limit!(&lua, None).unwrap();
+    lua.load("local t = {} for i = 1, 100000 do t[i] = tostring(i) end")
+        .exec()
+        .unwrap();
+}
+
 #[test]
 fn test_pluto_openlibs_all() {
     let lua = mlua::Lua::new();
@@ -60,3 +213,128 @@ fn test_pluto_openlibs_base32() {
         Err(e) => panic!("unexpected error: {e}"),
     }
 }
+
+/// Capabilities restricted by sandbox profiles, with code that returns a truthy value if the capability is available.
+const CAPABILITIES: &[(&str, &str)] = &[
+    ("filesystem", r#"return io.open("Cargo.toml")"#),
+    ("os.execute", "return os.execute()"),
+    ("binaries", r#"package.loadlib("", "*") return true"#),
+    ("compiled", "return load(string.dump(function() end))"),
+    ("unmoderated load", "return load(function() return nil end)"),
+    (
+        "http",
+        r#"local ok, err = pcall(require("pluto:http").request, "http://127.0.0.1:1/")
+        return ok or not tostring(err):find("disallowed by content moderation policy")"#,
+    ),
+    ("socket", r#"return require("pluto:socket")"#),
+    ("debug", "return debug"),
+];
+
+fn sandbox_capabilities() -> Vec<&'static str> {
+    let lua = mlua::Lua::new();
+    // the load hook is set globally by `test_pluto_hook`
+    pluto_ffi::set_policy!(&lua, pluto_ffi::hooks::Policy::new().load(|_: &str| true)).unwrap();
+    unsafe { lua.exec_raw::<()>((), |state| mlua::ffi::luaL_openlibs(state)) }.unwrap();
+
+    CAPABILITIES
+        .iter()
+        .filter(|(name, code)| {
+            let code = format!("local ok, res = pcall(function() {code} end) return ok and res");
+            match lua.load(code).set_name(*name).eval::<mlua::Value>() {
+                Ok(res) => !matches!(res, mlua::Value::Nil | mlua::Value::Boolean(false)),
+                Err(e) => panic!("unexpected error checking {name}: {e}"),
+            }
+        })
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+#[test]
+#[cfg(not(any(feature = "sandbox-strict", feature = "sandbox-network-only")))]
+fn test_sandbox_trusted() {
+    let all = CAPABILITIES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    assert_eq!(sandbox_capabilities(), all);
+}
+
+#[test]
+#[cfg(feature = "sandbox-strict")]
+fn test_sandbox_strict() {
+    assert_eq!(sandbox_capabilities(), Vec::<&str>::new());
+}
+
+#[test]
+#[cfg(feature = "sandbox-network-only")]
+fn test_sandbox_network_only() {
+    assert_eq!(sandbox_capabilities(), ["http", "socket"]);
+}
+
+#[test]
+fn test_pluto_binary() {
+    // the binary must be built with the same sandbox as the library
+    let script = CAPABILITIES
+        .iter()
+        .map(|(name, code)| {
+            format!("if (function() local ok, res = pcall(function() {code} end) return ok and res end)() then print({name:?}) end\n")
+        })
+        .collect::<String>();
+    let output = std::process::Command::new(env!("PLUTO_BIN"))
+        .arg("-e")
+        .arg(script)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), sandbox_capabilities());
+}
+
+#[cfg(not(any(feature = "sandbox-strict", feature = "sandbox-network-only")))]
+mod scripts {
+    include!(concat!(env!("OUT_DIR"), "/pluto_scripts.rs"));
+}
+
+#[test]
+#[cfg(not(any(feature = "sandbox-strict", feature = "sandbox-network-only")))]
+fn test_precompiled_scripts() {
+    assert_eq!(scripts::SCRIPTS, [("util.greet", scripts::UTIL_GREET)]);
+    assert!(scripts::UTIL_GREET.starts_with(b"\x1bLua"));
+
+    let lua = mlua::Lua::new();
+    let module = lua
+        .load(scripts::UTIL_GREET)
+        .set_name("util.greet")
+        .eval::<mlua::Table>()
+        .unwrap();
+    let greet = module.get::<mlua::Function>("greet").unwrap();
+    assert_eq!(greet.call::<String>("Pluto").unwrap(), "Hello, Pluto!");
+}
+
+#[test]
+fn test_script_bundle() {
+    // bundled as sources by the sandbox profiles, which disable the filesystem and compiled code
+    const BUNDLE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/pluto_bundle.bin"));
+    let lua = mlua::Lua::new();
+    pluto_ffi::install_bundle!(&lua, BUNDLE).unwrap();
+
+    let greeting = lua
+        .load(r#"return require("util.greet").greet("Pluto")"#)
+        .eval::<String>()
+        .unwrap();
+    assert_eq!(greeting, "Hello, Pluto!");
+    let err = lua.load(r#"require("util.missing")"#).exec().unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("no module 'util.missing' in the script bundle"));
+}
+
+#[test]
+#[cfg(all(target_env = "musl", target_feature = "crt-static"))]
+fn test_static_executable() {
+    // nothing may be loaded dynamically, so the tests run in an empty container
+    let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+    assert!(!maps.contains(".so"), "{maps}");
+    let binary = std::fs::read(env!("PLUTO_BIN")).unwrap();
+    assert!(!binary.windows(12).any(|w| w == b"/lib/ld-musl"));
+}
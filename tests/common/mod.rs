@@ -0,0 +1,22 @@
+//! Helpers for the tests that run pluto-build like a build script would.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Sets the environment cargo gives a build script compiling for `target` into `out_dir`.
+/// The environment is shared by the whole test binary, so each file using this has a single test.
+pub fn build_script_env(target: &str, out_dir: &Path) {
+    std::env::set_var("TARGET", target);
+    std::env::set_var("HOST", host());
+    std::env::set_var("OUT_DIR", out_dir);
+    std::env::set_var("OPT_LEVEL", "0");
+    std::env::set_var("DEBUG", "false");
+}
+
+/// The target triple of the machine running the tests.
+pub fn host() -> String {
+    let output = Command::new("rustc").arg("-vV").output().unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+    let host = output.lines().find_map(|line| line.strip_prefix("host: "));
+    host.unwrap().to_owned()
+}
@@ -0,0 +1,66 @@
+//! Cross-compiles Pluto for the non-x86 targets we support, like a build script would.
+//! This compiles Pluto once per target, so it only runs with `cargo test -p pluto-build -- --ignored`.
+//! Every target needs its cross compiler, e.g. install `g++-aarch64-linux-gnu` and `g++-arm-linux-gnueabihf`,
+//! or pick the targets with `PLUTO_CROSS_TARGETS=aarch64-unknown-linux-gnu`.
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+const TARGETS: &[(&str, &[&str])] = &[
+    (
+        "aarch64-unknown-linux-gnu",
+        &["soup", "soupintrin", "plutostatic"],
+    ),
+    // Soup has no intrinsics for 32-bit ARM
+    ("armv7-unknown-linux-gnueabihf", &["soup", "plutostatic"]),
+];
+
+#[test]
+#[ignore = "compiles Pluto for every target, run with `--ignored`"]
+fn cross_compile() {
+    let requested = std::env::var("PLUTO_CROSS_TARGETS").ok();
+    let requested = requested
+        .as_deref()
+        .map(|targets| targets.split(',').collect::<Vec<_>>());
+    if let Some(requested) = &requested {
+        for target in requested {
+            assert!(
+                TARGETS.iter().any(|&(t, _)| t == *target),
+                "{target} isn't a tested target"
+            );
+        }
+    }
+
+    for &(target, libs) in TARGETS {
+        if requested
+            .as_ref()
+            .is_some_and(|requested| !requested.contains(&target))
+        {
+            continue;
+        }
+        let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(target);
+        std::fs::create_dir_all(&out_dir).unwrap();
+        common::build_script_env(target, &out_dir);
+
+        let compiler = cc::Build::new().cpp(true).get_compiler();
+        assert!(
+            Command::new(compiler.path())
+                .arg("--version")
+                .output()
+                .is_ok(),
+            "{} not found for {target}, install it or leave {target} out of PLUTO_CROSS_TARGETS",
+            compiler.path().display()
+        );
+
+        pluto_build::Build::new().compile();
+        for lib in libs {
+            let file = out_dir.join(format!("lib{lib}.a"));
+            assert!(file.exists(), "{} is missing", file.display());
+        }
+        if !libs.contains(&"soupintrin") {
+            assert!(!out_dir.join("libsoupintrin.a").exists());
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Lints pluto-build and pluto-ffi with only some of the `lib-*` features, which the default build doesn't cover.
+//! This runs cargo once per combination, so it only runs with `cargo test -p pluto-build -- --ignored`.
+
+use std::path::Path;
+use std::process::Command;
+
+const FEATURES: &[&str] = &["", "lib-json,lib-base64", "lib-xml"];
+
+#[test]
+#[ignore = "runs cargo for every combination of features, run with `--ignored`"]
+fn library_features() {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("features");
+    for package in ["pluto-build", "pluto-ffi"] {
+        for features in FEATURES {
+            let status = Command::new(&cargo)
+                .args([
+                    "clippy",
+                    "--all-targets",
+                    "--no-default-features",
+                    "-p",
+                    package,
+                ])
+                .arg("--features")
+                .arg(features)
+                .arg("--manifest-path")
+                .arg(&manifest)
+                .arg("--target-dir")
+                .arg(&target_dir)
+                .args(["--", "-D", "warnings"])
+                .status()
+                .unwrap();
+            assert!(
+                status.success(),
+                "clippy failed for {package} with features `{features}`"
+            );
+        }
+    }
+}
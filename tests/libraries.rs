@@ -0,0 +1,39 @@
+//! Builds Pluto and its binaries with only some of its libraries, like a build script would, and runs a script against them.
+//! This compiles Pluto, so it only runs with `cargo test -p pluto-build -- --ignored`.
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use pluto_build::{Build, Library};
+
+const SCRIPT: &str = r#"
+assert(require("json").decode("[1]")[1] == 1)
+assert(require("base64").encode("a") == "YQ==")
+assert(not pcall(require, "xml"), "xml should have been left out")
+"#;
+
+#[test]
+#[ignore = "compiles Pluto, run with `--ignored`"]
+fn library_subset() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("libraries");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    common::build_script_env(&common::host(), &out_dir);
+
+    let mut build = Build::new();
+    build.libraries(&[Library::Json, Library::Base64]);
+    build.compile();
+    let binaries = build.compile_binaries();
+
+    let output = Command::new(&binaries.pluto)
+        .arg("-e")
+        .arg(SCRIPT)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}